@@ -1,24 +1,254 @@
+use std::collections::BTreeMap;
+
 use avian3d::prelude::*;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    definitions::{Registry, definitions_checksum},
+    floor::FLOOR_HEIGHT,
+    voxel::BlockId,
+};
+
+/// Folder in the assets directory holding the `*.block.ron` files
+pub const BLOCKS_FOLDER: &str = "blocks";
 
 pub const BLOCK_WIDTH: f32 = 1.0;
 pub const BLOCK_HEIGHT: f32 = 1.0;
 
+pub const CELL_SIZE: Vec3 = Vec3::new(BLOCK_WIDTH, BLOCK_HEIGHT, BLOCK_WIDTH);
+// The bottom row of cells rests on the floor
+const GRID_ORIGIN: Vec3 = Vec3::new(0.0, FLOOR_HEIGHT / 2.0, 0.0);
+
+/// Free-moving block, blocks placed on the grid are stored in a `voxel::VoxelWorld`
 #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct BlockMarker;
 
+/// The type of a free-moving block, peers build its physics and visuals from
+/// the registry
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockKind(pub BlockId);
+
+#[derive(Serialize, Deserialize, TypePath, Clone, Debug, PartialEq)]
+pub struct BlockType {
+    pub id: BlockId,
+    pub name: String,
+    // sRGB
+    pub color: (f32, f32, f32),
+    pub friction: f32,
+    pub restitution: f32,
+    pub density: f32,
+    // Blocks on the building grid are always static
+    pub rigid_body: RigidBody,
+    #[serde(default)]
+    pub surface: Surface,
+}
+
+/// How a block affects the characters standing on it, relative to plain ground
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct Surface {
+    /// Scales how fast characters speed up
+    pub acceleration: f32,
+    /// Scales their top speed
+    pub speed: f32,
+    /// Scales how fast they slow down without input
+    pub friction: f32,
+    /// Velocity given to characters standing on it, in the block's local space
+    pub conveyor: Vec3,
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self {
+            acceleration: 1.0,
+            speed: 1.0,
+            friction: 1.0,
+            conveyor: Vec3::ZERO,
+        }
+    }
+}
+
+impl Surface {
+    /// Fastest a character with the given top speed moves over this surface
+    pub fn top_speed(&self, max_speed: f32) -> f32 {
+        max_speed * self.speed + self.conveyor.length()
+    }
+}
+
+impl BlockType {
+    pub fn color(&self) -> Color {
+        let (red, green, blue) = self.color;
+        Color::srgb(red, green, blue)
+    }
+}
+
+/// Cell of the building grid containing `point`
+pub fn cell_at(point: Vec3) -> IVec3 {
+    ((point - GRID_ORIGIN) / CELL_SIZE).floor().as_ivec3()
+}
+
+pub fn cell_center(cell: IVec3) -> Vec3 {
+    GRID_ORIGIN + (cell.as_vec3() + 0.5) * CELL_SIZE
+}
+
+/// Surface properties of a block type, shared by free-moving blocks and chunks
+#[derive(Bundle, Clone)]
+pub struct BlockMaterialBundle {
+    friction: Friction,
+    restitution: Restitution,
+    density: ColliderDensity,
+    surface: Surface,
+}
+
+impl BlockMaterialBundle {
+    pub fn new(block_type: &BlockType) -> Self {
+        Self {
+            friction: Friction::new(block_type.friction),
+            restitution: Restitution::new(block_type.restitution),
+            density: ColliderDensity(block_type.density),
+            surface: block_type.surface,
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub struct BlockPhysicsBundle {
     collider: Collider,
     rigid_body: RigidBody,
+    material: BlockMaterialBundle,
 }
 
-impl Default for BlockPhysicsBundle {
-    fn default() -> Self {
+impl BlockPhysicsBundle {
+    pub fn new(block_type: &BlockType) -> Self {
         Self {
             collider: Collider::cuboid(BLOCK_WIDTH, BLOCK_HEIGHT, BLOCK_WIDTH),
-            rigid_body: RigidBody::Dynamic,
+            rigid_body: block_type.rigid_body,
+            material: BlockMaterialBundle::new(block_type),
         }
     }
 }
+
+#[derive(Error, Debug)]
+pub enum BlockRegistryError {
+    #[error("block id {0:?} is defined by both {1:?} and {2:?}")]
+    DuplicateId(BlockId, String, String),
+    #[error("block {0:?} uses the id of air")]
+    AirId(String),
+}
+
+/// Every block type the game knows about, loaded from [`BLOCKS_FOLDER`]
+#[derive(Resource, Debug, Default)]
+pub struct BlockRegistry {
+    // Ordered, so the checksum doesn't depend on load order
+    blocks: BTreeMap<BlockId, BlockType>,
+}
+
+impl BlockRegistry {
+    pub fn new(blocks: impl IntoIterator<Item = BlockType>) -> Result<Self, BlockRegistryError> {
+        let mut registry = Self::default();
+
+        for block in blocks {
+            if block.id.is_air() {
+                return Err(BlockRegistryError::AirId(block.name));
+            }
+            if let Some(existing) = registry.blocks.get(&block.id) {
+                return Err(BlockRegistryError::DuplicateId(
+                    block.id,
+                    existing.name.clone(),
+                    block.name,
+                ));
+            }
+            registry.blocks.insert(block.id, block);
+        }
+
+        Ok(registry)
+    }
+
+    pub fn get(&self, id: BlockId) -> Option<&BlockType> {
+        self.blocks.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BlockType> {
+        self.blocks.values()
+    }
+}
+
+impl Registry for BlockRegistry {
+    type Definition = BlockType;
+    type Error = BlockRegistryError;
+
+    const FOLDER: &'static str = BLOCKS_FOLDER;
+    const EXTENSIONS: &'static [&'static str] = &["block.ron"];
+    const NAME: &'static str = "block";
+
+    fn from_definitions(
+        blocks: impl IntoIterator<Item = BlockType>,
+    ) -> Result<Self, BlockRegistryError> {
+        Self::new(blocks)
+    }
+
+    fn count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn checksum(&self) -> u64 {
+        definitions_checksum(&self.blocks.values().collect::<Vec<_>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(id: u16, name: &str) -> BlockType {
+        BlockType {
+            id: BlockId(id),
+            name: name.to_string(),
+            color: (0.5, 0.5, 0.5),
+            friction: 0.5,
+            restitution: 0.0,
+            density: 1.0,
+            rigid_body: RigidBody::Static,
+            surface: Surface::default(),
+        }
+    }
+
+    #[test]
+    fn registry_holds_every_block() {
+        let registry = BlockRegistry::new([block(2, "Ice"), block(1, "Stone")]).unwrap();
+
+        assert_eq!(registry.count(), 2);
+        assert_eq!(registry.get(BlockId(1)), Some(&block(1, "Stone")));
+        assert_eq!(registry.get(BlockId(3)), None);
+    }
+
+    #[test]
+    fn blocks_cant_use_the_id_of_air() {
+        let result = BlockRegistry::new([block(1, "Stone"), block(0, "Void")]);
+
+        assert!(matches!(result, Err(BlockRegistryError::AirId(name)) if name == "Void"));
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected() {
+        let result = BlockRegistry::new([block(1, "Stone"), block(1, "Granite")]);
+
+        assert!(matches!(
+            result,
+            Err(BlockRegistryError::DuplicateId(BlockId(1), existing, duplicate))
+                if existing == "Stone" && duplicate == "Granite"
+        ));
+    }
+
+    #[test]
+    fn checksum_doesnt_depend_on_load_order() {
+        let registry = BlockRegistry::new([block(1, "Stone"), block(2, "Ice")]).unwrap();
+        let reversed = BlockRegistry::new([block(2, "Ice"), block(1, "Stone")]).unwrap();
+        let changed = BlockRegistry::new([block(1, "Stone"), block(2, "Snow")]).unwrap();
+
+        assert_eq!(registry.checksum(), reversed.checksum());
+        assert_ne!(registry.checksum(), changed.checksum());
+    }
+}
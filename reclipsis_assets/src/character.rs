@@ -8,28 +8,42 @@ pub const CHARACTER_CAPSULE_HEIGHT: f32 = 0.5;
 #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct CharacterMarker;
 
+/// Jump timing of a character, counted in ticks. It's predicted, so rollbacks
+/// replay buffered and late jumps exactly like the server
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct JumpState {
+    /// Ticks since the character last stood on walkable ground
+    pub ticks_since_grounded: u8,
+    /// Ticks since a jump press that hasn't been used yet
+    pub buffered_jump: Option<u8>,
+    /// Rising from a jump whose button is still held
+    pub rising: bool,
+}
+
+impl Default for JumpState {
+    fn default() -> Self {
+        Self {
+            // Characters spawn in the air
+            ticks_since_grounded: u8::MAX,
+            buffered_jump: None,
+            rising: false,
+        }
+    }
+}
+
+/// Characters are moved by the character controller in `reclipsis_common`
+/// rather than by forces, so their bodies are kinematic
 #[derive(Bundle)]
 pub struct CharacterPhysicsBundle {
     collider: Collider,
     rigid_body: RigidBody,
-    external_force: ExternalForce,
-    external_impulse: ExternalImpulse,
-    lock_axes: LockedAxes,
-    friction: Friction,
 }
 
 impl Default for CharacterPhysicsBundle {
     fn default() -> Self {
         Self {
             collider: Collider::capsule(CHARACTER_CAPSULE_RADIUS, CHARACTER_CAPSULE_HEIGHT),
-            rigid_body: RigidBody::Dynamic,
-            external_force: ExternalForce::ZERO.with_persistence(false),
-            external_impulse: ExternalImpulse::ZERO.with_persistence(false),
-            lock_axes: LockedAxes::default()
-                .lock_rotation_x()
-                .lock_rotation_y()
-                .lock_rotation_z(),
-            friction: Friction::new(0.0).with_combine_rule(CoefficientCombine::Min),
+            rigid_body: RigidBody::Kinematic,
         }
     }
 }
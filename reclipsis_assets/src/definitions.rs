@@ -0,0 +1,301 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedFolder, io::Reader},
+    prelude::*,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+/// Resource built from the definitions in every matching file of an asset folder
+pub trait Registry: Resource + Sized {
+    type Definition: Serialize + DeserializeOwned + TypePath + Clone + Send + Sync;
+    type Error: std::error::Error;
+
+    /// Folder in the assets directory holding the definition files
+    const FOLDER: &'static str;
+    const EXTENSIONS: &'static [&'static str];
+    /// What one definition is called in the logs
+    const NAME: &'static str;
+
+    fn from_definitions(
+        definitions: impl IntoIterator<Item = Self::Definition>,
+    ) -> Result<Self, Self::Error>;
+
+    fn count(&self) -> usize;
+
+    /// Hash of all definitions, compared between client and server
+    fn checksum(&self) -> u64;
+}
+
+/// Loads the definitions of `R` and inserts the registry once all are loaded
+pub struct RegistryPlugin<R>(PhantomData<fn() -> R>);
+
+impl<R> Default for RegistryPlugin<R> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<R: Registry> Plugin for RegistryPlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Definitions<R::Definition>>()
+            .register_asset_loader(DefinitionsLoader::<R>(PhantomData))
+            .add_systems(Startup, load_definitions::<R>)
+            .add_systems(
+                Update,
+                build_registry::<R>.run_if(not(resource_exists::<R>)),
+            );
+    }
+}
+
+/// Contents of one definitions file
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct Definitions<T: TypePath + Send + Sync>(pub Vec<T>);
+
+/// FNV-1a hash of serialized definitions
+pub fn definitions_checksum(definitions: &impl Serialize) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let definitions = ron::to_string(definitions).expect("definitions serialize");
+
+    definitions.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+#[derive(Error, Debug)]
+pub enum DefinitionsLoaderError {
+    #[error("failed to read definitions: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse definitions: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+struct DefinitionsLoader<R>(PhantomData<fn() -> R>);
+
+impl<R: Registry> AssetLoader for DefinitionsLoader<R> {
+    type Asset = Definitions<R::Definition>;
+    type Settings = ();
+    type Error = DefinitionsLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(Definitions(ron::de::from_bytes(&bytes)?))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        R::EXTENSIONS
+    }
+}
+
+#[derive(Resource)]
+struct DefinitionsFolder<R: Registry> {
+    handle: Handle<LoadedFolder>,
+    marker: PhantomData<fn() -> R>,
+}
+
+fn load_definitions<R: Registry>(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(DefinitionsFolder::<R> {
+        handle: asset_server.load_folder(R::FOLDER),
+        marker: PhantomData,
+    });
+}
+
+fn build_registry<R: Registry>(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    folder: Res<DefinitionsFolder<R>>,
+    folders: Res<Assets<LoadedFolder>>,
+    definitions: Res<Assets<Definitions<R::Definition>>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if asset_server.load_state(&folder.handle).is_failed() {
+        error!("Failed to load the {:?} asset folder", R::FOLDER);
+        exit.write(AppExit::error());
+        return;
+    }
+    if !asset_server.is_loaded_with_dependencies(&folder.handle) {
+        return;
+    }
+    let Some(folder) = folders.get(&folder.handle) else {
+        return;
+    };
+
+    let loaded = folder
+        .handles
+        .iter()
+        .filter_map(|handle| {
+            handle
+                .clone()
+                .try_typed::<Definitions<R::Definition>>()
+                .ok()
+        })
+        .filter_map(|handle| definitions.get(&handle))
+        .flat_map(|definitions| definitions.0.iter().cloned());
+
+    match R::from_definitions(loaded) {
+        Ok(registry) => {
+            info!(
+                "Loaded {} {} definitions, checksum {:016x}",
+                registry.count(),
+                R::NAME,
+                registry.checksum()
+            );
+            commands.insert_resource(registry);
+        }
+        Err(err) => {
+            error!("Invalid {} definitions: {err}", R::NAME);
+            exit.write(AppExit::error());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, TypePath, Clone, Debug, PartialEq)]
+    struct Named {
+        id: u32,
+        name: String,
+    }
+
+    #[derive(Error, Debug)]
+    #[error("id {0} is defined twice")]
+    struct DuplicateId(u32);
+
+    #[derive(Resource, Debug)]
+    struct Names(Vec<Named>);
+
+    impl Registry for Names {
+        type Definition = Named;
+        type Error = DuplicateId;
+
+        const FOLDER: &'static str = "names";
+        const EXTENSIONS: &'static [&'static str] = &["name.ron"];
+        const NAME: &'static str = "name";
+
+        fn from_definitions(
+            definitions: impl IntoIterator<Item = Named>,
+        ) -> Result<Self, DuplicateId> {
+            let mut names: Vec<Named> = definitions.into_iter().collect();
+            names.sort_by_key(|named| named.id);
+            if let Some(pair) = names.windows(2).find(|pair| pair[0].id == pair[1].id) {
+                return Err(DuplicateId(pair[0].id));
+            }
+            Ok(Self(names))
+        }
+
+        fn count(&self) -> usize {
+            self.0.len()
+        }
+
+        fn checksum(&self) -> u64 {
+            definitions_checksum(&self.0)
+        }
+    }
+
+    fn named(id: u32, name: &str) -> Named {
+        Named {
+            id,
+            name: name.to_string(),
+        }
+    }
+
+    /// Asset directory with the given files in the `names` folder
+    fn assets(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("reclipsis_definitions_{test}"));
+        let folder = root.join(Names::FOLDER);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&folder).unwrap();
+        for (file, contents) in files {
+            fs::write(folder.join(file), contents).unwrap();
+        }
+        root
+    }
+
+    /// Runs the app until the registry is built or it exits
+    fn load(root: PathBuf) -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: root.to_string_lossy().into_owned(),
+                ..default()
+            },
+            RegistryPlugin::<Names>::default(),
+        ));
+
+        for _ in 0..500 {
+            app.update();
+            if app.world().contains_resource::<Names>() || app.should_exit().is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        app
+    }
+
+    #[test]
+    fn registry_holds_the_definitions_of_every_matching_file() {
+        let root = assets(
+            "every_file",
+            &[
+                (
+                    "animals.name.ron",
+                    r#"[(id: 2, name: "Cat"), (id: 1, name: "Dog")]"#,
+                ),
+                ("plants.name.ron", r#"[(id: 3, name: "Fern")]"#),
+            ],
+        );
+
+        let app = load(root);
+
+        let names = app.world().resource::<Names>();
+        assert_eq!(
+            names.0,
+            [named(1, "Dog"), named(2, "Cat"), named(3, "Fern")]
+        );
+        // The same definitions hash the same no matter the files they came from
+        let expected =
+            Names::from_definitions([named(3, "Fern"), named(2, "Cat"), named(1, "Dog")]).unwrap();
+        assert_eq!(names.checksum(), expected.checksum());
+    }
+
+    #[test]
+    fn invalid_definitions_exit_the_app() {
+        let root = assets(
+            "duplicates",
+            &[
+                ("animals.name.ron", r#"[(id: 1, name: "Cat")]"#),
+                ("more_animals.name.ron", r#"[(id: 1, name: "Dog")]"#),
+            ],
+        );
+
+        let app = load(root);
+
+        assert!(!app.world().contains_resource::<Names>());
+        assert!(app.should_exit().is_some_and(|exit| exit.is_error()));
+    }
+
+    #[test]
+    fn checksums_differ_with_the_definitions() {
+        let cat = definitions_checksum(&[named(1, "Cat")]);
+
+        assert_eq!(cat, definitions_checksum(&[named(1, "Cat")]));
+        assert_ne!(cat, definitions_checksum(&[named(1, "Dog")]));
+        assert_ne!(cat, definitions_checksum(&[named(2, "Cat")]));
+    }
+}
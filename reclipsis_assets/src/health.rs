@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_HEALTH: f32 = 100.0;
+
+/// Damage is only dealt by the server, healing with items is predicted
+#[derive(Component, Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            current: MAX_HEALTH,
+            max: MAX_HEALTH,
+        }
+    }
+}
+
+impl Health {
+    pub fn is_depleted(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn fraction(&self) -> f32 {
+        (self.current / self.max).clamp(0.0, 1.0)
+    }
+
+    /// Returns the damage actually taken
+    pub fn damage(&mut self, amount: f32) -> f32 {
+        let taken = amount.clamp(0.0, self.current.max(0.0));
+        self.current -= taken;
+        taken
+    }
+
+    /// Returns the health actually restored
+    pub fn heal(&mut self, amount: f32) -> f32 {
+        let healed = amount.clamp(0.0, (self.max - self.current).max(0.0));
+        self.current += healed;
+        healed
+    }
+}
+
+/// Characters with no health left, they can't act until the server respawns them
+#[derive(Component, Serialize, Deserialize, Reflect, Clone, Debug, PartialEq)]
+pub struct Dead {
+    /// What killed the character, like "Player 1 with Sword"
+    pub cause: String,
+    pub respawn_delay_secs: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(current: f32) -> Health {
+        Health {
+            current,
+            max: MAX_HEALTH,
+        }
+    }
+
+    #[test]
+    fn damage_returns_the_damage_taken() {
+        let mut target = health(50.0);
+
+        assert_eq!(target.damage(20.0), 20.0);
+        assert_eq!(target.current, 30.0);
+        assert!(!target.is_depleted());
+    }
+
+    #[test]
+    fn damage_stops_at_zero() {
+        let mut target = health(10.0);
+
+        assert_eq!(target.damage(25.0), 10.0);
+        assert_eq!(target.current, 0.0);
+        assert!(target.is_depleted());
+
+        assert_eq!(target.damage(5.0), 0.0);
+        assert_eq!(target.current, 0.0);
+    }
+
+    #[test]
+    fn heal_returns_the_health_restored() {
+        let mut target = health(50.0);
+
+        assert_eq!(target.heal(20.0), 20.0);
+        assert_eq!(target.current, 70.0);
+    }
+
+    #[test]
+    fn heal_stops_at_max() {
+        let mut target = health(90.0);
+
+        assert_eq!(target.heal(25.0), 10.0);
+        assert_eq!(target.current, MAX_HEALTH);
+
+        assert_eq!(target.heal(5.0), 0.0);
+        assert_eq!(target.current, MAX_HEALTH);
+    }
+
+    #[test]
+    fn negative_amounts_change_nothing() {
+        let mut target = health(50.0);
+
+        assert_eq!(target.damage(-10.0), 0.0);
+        assert_eq!(target.heal(-10.0), 0.0);
+        assert_eq!(target.current, 50.0);
+    }
+
+    #[test]
+    fn out_of_range_health_doesnt_panic() {
+        let mut overhealed = health(MAX_HEALTH + 10.0);
+        assert_eq!(overhealed.heal(5.0), 0.0);
+        assert_eq!(overhealed.current, MAX_HEALTH + 10.0);
+
+        let mut overkilled = health(-5.0);
+        assert_eq!(overkilled.damage(5.0), 0.0);
+        assert_eq!(overkilled.current, -5.0);
+        assert_eq!(overkilled.fraction(), 0.0);
+    }
+}
@@ -1,21 +1,549 @@
-use std::collections::HashMap;
-
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-#[derive(Component, Serialize, Deserialize, Reflect, Clone, Debug, PartialEq, Default)]
+use crate::item::ItemRegistry;
+
+pub const INVENTORY_SLOTS: usize = 27;
+/// The first slots of the inventory, the ones that can be equipped
+pub const HOTBAR_SLOTS: usize = 9;
+
+/// Fixed number of slots holding item stacks, only changed by the server
+#[derive(Component, Serialize, Deserialize, Reflect, Clone, Debug, PartialEq)]
 pub struct Inventory {
-    // HashMap<InventorySlot, ItemId>
-    // Slot 0 is ignored
-    pub inventory: HashMap<u8, ItemId>,
-    pub equipped_item: Option<ItemId>,
+    slots: Vec<Option<ItemStack>>,
+}
+
+/// Hotbar slot the character holds, the slot may be empty. Changed by inputs, so
+/// unlike the inventory it is rolled back.
+#[derive(Component, Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq, Default)]
+pub struct EquippedSlot(pub Option<usize>);
+
+impl EquippedSlot {
+    pub fn stack<'a>(&self, inventory: &'a Inventory) -> Option<&'a ItemStack> {
+        self.0.and_then(|slot| inventory.get(slot))
+    }
+
+    /// Equips `slot`, or unequips it if it already is
+    pub fn toggle(&mut self, slot: usize) {
+        self.0 = if self.0 == Some(slot) {
+            None
+        } else {
+            Some(slot)
+        };
+    }
+
+    /// Moves `offset` slots through the hotbar, wrapping around
+    pub fn cycle(&mut self, offset: isize) {
+        let hotbar_slots = HOTBAR_SLOTS as isize;
+        let slot = match self.0 {
+            Some(slot) => slot as isize + offset,
+            // Nothing equipped counts as sitting right before the first slot
+            None if offset > 0 => offset - 1,
+            None => hotbar_slots + offset,
+        };
+        self.0 = Some(slot.rem_euclid(hotbar_slots) as usize);
+    }
+}
+
+/// Fixed ticks left until each item can be used again. Counted down by the
+/// simulation, so it is rolled back like the equipped slot.
+#[derive(Component, Serialize, Deserialize, Reflect, Clone, Debug, PartialEq, Default)]
+pub struct ItemCooldowns(Vec<(ItemId, u32)>);
+
+impl ItemCooldowns {
+    pub fn remaining(&self, id: ItemId) -> u32 {
+        self.0
+            .iter()
+            .find(|(cooldown_id, _)| *cooldown_id == id)
+            .map_or(0, |(_, ticks)| *ticks)
+    }
+
+    pub fn is_ready(&self, id: ItemId) -> bool {
+        self.remaining(id) == 0
+    }
+
+    pub fn start(&mut self, id: ItemId, ticks: u32) {
+        self.0.retain(|(cooldown_id, _)| *cooldown_id != id);
+        if ticks > 0 {
+            self.0.push((id, ticks));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Advances all cooldowns by one tick
+    pub fn tick(&mut self) {
+        for (_, ticks) in &mut self.0 {
+            *ticks -= 1;
+        }
+        self.0.retain(|(_, ticks)| *ticks > 0);
+    }
 }
 
-#[derive(Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq)]
+/// Key into `item::ItemRegistry`
+#[derive(
+    Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
 pub struct ItemId(pub u32);
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct Item {
+#[derive(Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ItemStack {
     pub id: ItemId,
-    pub name: String,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(id: ItemId, count: u32) -> Self {
+        Self { id, count }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InventoryError {
+    #[error("slot {0} doesn't exist")]
+    InvalidSlot(usize),
+    #[error("slot {0} is empty")]
+    EmptySlot(usize),
+    #[error("slot {0} is occupied")]
+    OccupiedSlot(usize),
+    #[error("source and destination are both slot {0}")]
+    SameSlot(usize),
+    #[error("item {0:?} isn't in the item registry")]
+    UnknownItem(ItemId),
+    #[error("item counts must be above zero")]
+    ZeroCount,
+    #[error("slot {slot} holds {available}, can't take {requested}")]
+    NotEnough {
+        slot: usize,
+        requested: u32,
+        available: u32,
+    },
+    #[error("slots hold different items")]
+    DifferentItems,
+    #[error("stack in slot {0} is full")]
+    StackFull(usize),
+    #[error("not enough room for {0} more items")]
+    Full(u32),
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            slots: vec![None; INVENTORY_SLOTS],
+        }
+    }
+}
+
+impl Inventory {
+    pub fn get(&self, slot: usize) -> Option<&ItemStack> {
+        self.slots.get(slot).and_then(Option::as_ref)
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    pub fn first_empty_slot(&self) -> Option<usize> {
+        self.slots.iter().position(Option::is_none)
+    }
+
+    fn check_slot(&self, slot: usize) -> Result<(), InventoryError> {
+        if slot < self.slots.len() {
+            Ok(())
+        } else {
+            Err(InventoryError::InvalidSlot(slot))
+        }
+    }
+
+    fn stack(&self, slot: usize) -> Result<ItemStack, InventoryError> {
+        self.check_slot(slot)?;
+        self.slots[slot].ok_or(InventoryError::EmptySlot(slot))
+    }
+
+    /// Tops up existing stacks of the item first, then fills empty slots. Nothing
+    /// changes unless the whole stack fits.
+    pub fn add(&mut self, registry: &ItemRegistry, stack: ItemStack) -> Result<(), InventoryError> {
+        if stack.count == 0 {
+            return Err(InventoryError::ZeroCount);
+        }
+        let max_stack = registry
+            .get(stack.id)
+            .ok_or(InventoryError::UnknownItem(stack.id))?
+            .max_stack;
+
+        let room: u32 = self
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                None => max_stack,
+                Some(existing) if existing.id == stack.id => {
+                    max_stack.saturating_sub(existing.count)
+                }
+                Some(_) => 0,
+            })
+            .sum();
+        if room < stack.count {
+            return Err(InventoryError::Full(stack.count - room));
+        }
+
+        let mut remaining = stack.count;
+        for existing in self.slots.iter_mut().flatten() {
+            if existing.id == stack.id && existing.count < max_stack {
+                let moved = remaining.min(max_stack - existing.count);
+                existing.count += moved;
+                remaining -= moved;
+            }
+        }
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if remaining == 0 {
+                break;
+            }
+            let moved = remaining.min(max_stack);
+            *slot = Some(ItemStack::new(stack.id, moved));
+            remaining -= moved;
+        }
+
+        Ok(())
+    }
+
+    /// Takes `count` items out of `slot`
+    pub fn remove(&mut self, slot: usize, count: u32) -> Result<ItemStack, InventoryError> {
+        if count == 0 {
+            return Err(InventoryError::ZeroCount);
+        }
+        let stack = self.stack(slot)?;
+        if stack.count < count {
+            return Err(InventoryError::NotEnough {
+                slot,
+                requested: count,
+                available: stack.count,
+            });
+        }
+
+        self.slots[slot] =
+            (stack.count > count).then(|| ItemStack::new(stack.id, stack.count - count));
+        Ok(ItemStack::new(stack.id, count))
+    }
+
+    /// Moves the stack in `from` into the empty slot `to`
+    pub fn move_stack(&mut self, from: usize, to: usize) -> Result<(), InventoryError> {
+        if from == to {
+            return Err(InventoryError::SameSlot(from));
+        }
+        let stack = self.stack(from)?;
+        self.check_slot(to)?;
+        if self.slots[to].is_some() {
+            return Err(InventoryError::OccupiedSlot(to));
+        }
+
+        self.slots[to] = Some(stack);
+        self.slots[from] = None;
+        Ok(())
+    }
+
+    /// Swaps the contents of two slots, either may be empty
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<(), InventoryError> {
+        if a == b {
+            return Err(InventoryError::SameSlot(a));
+        }
+        self.check_slot(a)?;
+        self.check_slot(b)?;
+
+        self.slots.swap(a, b);
+        Ok(())
+    }
+
+    /// Moves `count` items of the stack in `from` into the empty slot `to`
+    pub fn split(&mut self, from: usize, to: usize, count: u32) -> Result<(), InventoryError> {
+        if from == to {
+            return Err(InventoryError::SameSlot(from));
+        }
+        let stack = self.stack(from)?;
+        self.check_slot(to)?;
+        if self.slots[to].is_some() {
+            return Err(InventoryError::OccupiedSlot(to));
+        }
+        // Taking the whole stack is a move, not a split
+        if count >= stack.count {
+            return Err(InventoryError::NotEnough {
+                slot: from,
+                requested: count,
+                available: stack.count,
+            });
+        }
+
+        let split = self.remove(from, count)?;
+        self.slots[to] = Some(split);
+        Ok(())
+    }
+
+    /// Moves as many items from `from` onto the stack of the same item in `to` as fit,
+    /// the rest stays in `from`
+    pub fn merge(
+        &mut self,
+        registry: &ItemRegistry,
+        from: usize,
+        to: usize,
+    ) -> Result<(), InventoryError> {
+        if from == to {
+            return Err(InventoryError::SameSlot(from));
+        }
+        let source = self.stack(from)?;
+        let destination = self.stack(to)?;
+        if source.id != destination.id {
+            return Err(InventoryError::DifferentItems);
+        }
+        let max_stack = registry
+            .get(source.id)
+            .ok_or(InventoryError::UnknownItem(source.id))?
+            .max_stack;
+        if destination.count >= max_stack {
+            return Err(InventoryError::StackFull(to));
+        }
+
+        let moved = source.count.min(max_stack - destination.count);
+        self.remove(from, moved)?;
+        self.slots[to] = Some(ItemStack::new(destination.id, destination.count + moved));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        item::{Item, ItemKind},
+        voxel::BlockId,
+    };
+
+    const SWORD: ItemId = ItemId(1);
+    const BLOCK: ItemId = ItemId(2);
+
+    fn registry() -> ItemRegistry {
+        let item = |id, name: &str, max_stack| Item {
+            id,
+            name: name.to_string(),
+            description: String::new(),
+            icon: String::new(),
+            max_stack,
+            kind: ItemKind::Block { block: BlockId(1) },
+        };
+
+        ItemRegistry::new([item(SWORD, "Sword", 1), item(BLOCK, "Block", 64)]).unwrap()
+    }
+
+    fn inventory(slots: &[(usize, ItemId, u32)]) -> Inventory {
+        let mut inventory = Inventory::default();
+        for (slot, id, count) in slots {
+            inventory.slots[*slot] = Some(ItemStack::new(*id, *count));
+        }
+        inventory
+    }
+
+    #[test]
+    fn add_tops_up_stacks_before_filling_empty_slots() {
+        let registry = registry();
+        let mut inventory = inventory(&[(3, BLOCK, 60)]);
+
+        inventory.add(&registry, ItemStack::new(BLOCK, 70)).unwrap();
+
+        assert_eq!(inventory.get(3), Some(&ItemStack::new(BLOCK, 64)));
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(BLOCK, 64)));
+        assert_eq!(inventory.get(1), Some(&ItemStack::new(BLOCK, 2)));
+    }
+
+    #[test]
+    fn add_respects_max_stack() {
+        let registry = registry();
+        let mut inventory = Inventory::default();
+
+        inventory.add(&registry, ItemStack::new(SWORD, 2)).unwrap();
+
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(SWORD, 1)));
+        assert_eq!(inventory.get(1), Some(&ItemStack::new(SWORD, 1)));
+    }
+
+    #[test]
+    fn add_to_full_inventory_changes_nothing() {
+        let registry = registry();
+        let mut inventory = Inventory::default();
+        inventory
+            .add(&registry, ItemStack::new(SWORD, INVENTORY_SLOTS as u32 - 1))
+            .unwrap();
+        let before = inventory.clone();
+
+        assert_eq!(
+            inventory.add(&registry, ItemStack::new(BLOCK, 65)),
+            Err(InventoryError::Full(1))
+        );
+        assert_eq!(inventory, before);
+    }
+
+    #[test]
+    fn add_rejects_unknown_items_and_zero_counts() {
+        let registry = registry();
+        let mut inventory = Inventory::default();
+
+        assert_eq!(
+            inventory.add(&registry, ItemStack::new(ItemId(99), 1)),
+            Err(InventoryError::UnknownItem(ItemId(99)))
+        );
+        assert_eq!(
+            inventory.add(&registry, ItemStack::new(BLOCK, 0)),
+            Err(InventoryError::ZeroCount)
+        );
+    }
+
+    #[test]
+    fn remove_takes_part_or_all_of_a_stack() {
+        let mut inventory = inventory(&[(0, BLOCK, 10)]);
+
+        assert_eq!(inventory.remove(0, 4), Ok(ItemStack::new(BLOCK, 4)));
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(BLOCK, 6)));
+
+        assert_eq!(
+            inventory.remove(0, 7),
+            Err(InventoryError::NotEnough {
+                slot: 0,
+                requested: 7,
+                available: 6
+            })
+        );
+
+        assert_eq!(inventory.remove(0, 6), Ok(ItemStack::new(BLOCK, 6)));
+        assert_eq!(inventory.get(0), None);
+        assert_eq!(inventory.remove(0, 1), Err(InventoryError::EmptySlot(0)));
+        assert_eq!(
+            inventory.remove(INVENTORY_SLOTS, 1),
+            Err(InventoryError::InvalidSlot(INVENTORY_SLOTS))
+        );
+    }
+
+    #[test]
+    fn move_stack_needs_an_empty_destination() {
+        let mut inventory = inventory(&[(0, BLOCK, 10), (1, SWORD, 1)]);
+
+        assert_eq!(
+            inventory.move_stack(0, 1),
+            Err(InventoryError::OccupiedSlot(1))
+        );
+        assert_eq!(inventory.move_stack(0, 0), Err(InventoryError::SameSlot(0)));
+
+        inventory.move_stack(0, 5).unwrap();
+        assert_eq!(inventory.get(0), None);
+        assert_eq!(inventory.get(5), Some(&ItemStack::new(BLOCK, 10)));
+    }
+
+    #[test]
+    fn swap_exchanges_slots() {
+        let mut inventory = inventory(&[(0, BLOCK, 10), (1, SWORD, 1)]);
+
+        inventory.swap(0, 1).unwrap();
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(SWORD, 1)));
+        assert_eq!(inventory.get(1), Some(&ItemStack::new(BLOCK, 10)));
+
+        inventory.swap(1, 2).unwrap();
+        assert_eq!(inventory.get(1), None);
+        assert_eq!(inventory.get(2), Some(&ItemStack::new(BLOCK, 10)));
+    }
+
+    #[test]
+    fn split_leaves_part_of_the_stack_behind() {
+        let mut inventory = inventory(&[(0, BLOCK, 10)]);
+
+        inventory.split(0, 1, 3).unwrap();
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(BLOCK, 7)));
+        assert_eq!(inventory.get(1), Some(&ItemStack::new(BLOCK, 3)));
+
+        assert_eq!(
+            inventory.split(0, 2, 7),
+            Err(InventoryError::NotEnough {
+                slot: 0,
+                requested: 7,
+                available: 7
+            })
+        );
+        assert_eq!(
+            inventory.split(0, 1, 1),
+            Err(InventoryError::OccupiedSlot(1))
+        );
+    }
+
+    #[test]
+    fn merge_fills_the_destination_up_to_max_stack() {
+        let registry = registry();
+        let mut inventory = inventory(&[(0, BLOCK, 10), (1, BLOCK, 60), (2, SWORD, 1)]);
+
+        inventory.merge(&registry, 0, 1).unwrap();
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(BLOCK, 6)));
+        assert_eq!(inventory.get(1), Some(&ItemStack::new(BLOCK, 64)));
+
+        assert_eq!(
+            inventory.merge(&registry, 0, 1),
+            Err(InventoryError::StackFull(1))
+        );
+        assert_eq!(
+            inventory.merge(&registry, 0, 2),
+            Err(InventoryError::DifferentItems)
+        );
+
+        inventory.merge(&registry, 1, 0).unwrap();
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(BLOCK, 64)));
+        assert_eq!(inventory.get(1), Some(&ItemStack::new(BLOCK, 6)));
+    }
+
+    #[test]
+    fn equipped_follows_the_slot() {
+        let mut inventory = inventory(&[(2, SWORD, 1)]);
+        let equipped = EquippedSlot(Some(2));
+        assert_eq!(equipped.stack(&inventory), Some(&ItemStack::new(SWORD, 1)));
+
+        inventory.move_stack(2, 3).unwrap();
+        assert_eq!(equipped.stack(&inventory), None);
+    }
+
+    #[test]
+    fn equipping_the_equipped_slot_unequips() {
+        let mut equipped = EquippedSlot::default();
+
+        equipped.toggle(4);
+        assert_eq!(equipped, EquippedSlot(Some(4)));
+        equipped.toggle(2);
+        assert_eq!(equipped, EquippedSlot(Some(2)));
+        equipped.toggle(2);
+        assert_eq!(equipped, EquippedSlot(None));
+    }
+
+    #[test]
+    fn cooldowns_count_down_per_item() {
+        let mut cooldowns = ItemCooldowns::default();
+        cooldowns.start(SWORD, 2);
+        cooldowns.start(BLOCK, 1);
+
+        cooldowns.tick();
+        assert_eq!(cooldowns.remaining(SWORD), 1);
+        assert!(cooldowns.is_ready(BLOCK));
+
+        cooldowns.tick();
+        assert!(cooldowns.is_ready(SWORD));
+        assert_eq!(cooldowns, ItemCooldowns::default());
+    }
+
+    #[test]
+    fn cycling_wraps_around_the_hotbar() {
+        let mut equipped = EquippedSlot::default();
+        equipped.cycle(1);
+        assert_eq!(equipped, EquippedSlot(Some(0)));
+        equipped.cycle(-1);
+        assert_eq!(equipped, EquippedSlot(Some(HOTBAR_SLOTS - 1)));
+        equipped.cycle(1);
+        assert_eq!(equipped, EquippedSlot(Some(0)));
+
+        let mut equipped = EquippedSlot::default();
+        equipped.cycle(-1);
+        assert_eq!(equipped, EquippedSlot(Some(HOTBAR_SLOTS - 1)));
+    }
 }
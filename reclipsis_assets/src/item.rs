@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    definitions::{Registry, definitions_checksum},
+    inventory::ItemId,
+    voxel::BlockId,
+};
+
+/// Folder in the assets directory holding the `*.item.ron` files
+pub const ITEMS_FOLDER: &str = "items";
+
+#[derive(Serialize, Deserialize, TypePath, Clone, Debug, PartialEq)]
+pub struct Item {
+    pub id: ItemId,
+    pub name: String,
+    pub description: String,
+    // Relative to the assets directory
+    pub icon: String,
+    pub max_stack: u32,
+    pub kind: ItemKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ItemKind {
+    Melee {
+        damage: f32,
+        range: f32,
+        cooldown_secs: f32,
+    },
+    Ranged {
+        damage: f32,
+        projectile_speed: f32,
+        // 0 flies straight, 1 falls like everything else
+        gravity_scale: f32,
+        lifetime_secs: f32,
+        cooldown_secs: f32,
+    },
+    /// Placed on the building grid as `block`
+    Block {
+        block: BlockId,
+    },
+    Consumable {
+        heal: f32,
+    },
+}
+
+impl ItemKind {
+    /// Time between uses, items without one can be used every tick
+    pub fn cooldown_secs(&self) -> f32 {
+        match self {
+            Self::Melee { cooldown_secs, .. } | Self::Ranged { cooldown_secs, .. } => {
+                *cooldown_secs
+            }
+            Self::Block { .. } | Self::Consumable { .. } => 0.0,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ItemRegistryError {
+    #[error("item id {0:?} is defined by both {1:?} and {2:?}")]
+    DuplicateId(ItemId, String, String),
+    #[error("item {0:?} has a max stack size of 0")]
+    EmptyStack(String),
+}
+
+/// Every item the game knows about, loaded from [`ITEMS_FOLDER`]
+#[derive(Resource, Debug, Default)]
+pub struct ItemRegistry {
+    // Ordered, so the checksum doesn't depend on load order
+    items: BTreeMap<ItemId, Item>,
+}
+
+impl ItemRegistry {
+    pub fn new(items: impl IntoIterator<Item = Item>) -> Result<Self, ItemRegistryError> {
+        let mut registry = Self::default();
+
+        for item in items {
+            if item.max_stack == 0 {
+                return Err(ItemRegistryError::EmptyStack(item.name));
+            }
+            if let Some(existing) = registry.items.get(&item.id) {
+                return Err(ItemRegistryError::DuplicateId(
+                    item.id,
+                    existing.name.clone(),
+                    item.name,
+                ));
+            }
+            registry.items.insert(item.id, item);
+        }
+
+        Ok(registry)
+    }
+
+    pub fn get(&self, id: ItemId) -> Option<&Item> {
+        self.items.get(&id)
+    }
+
+    /// The item that places `block`, given back when the block is removed
+    pub fn block_item(&self, block: BlockId) -> Option<ItemId> {
+        self.items
+            .values()
+            .find(|item| item.kind == ItemKind::Block { block })
+            .map(|item| item.id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Item> {
+        self.items.values()
+    }
+}
+
+impl Registry for ItemRegistry {
+    type Definition = Item;
+    type Error = ItemRegistryError;
+
+    const FOLDER: &'static str = ITEMS_FOLDER;
+    const EXTENSIONS: &'static [&'static str] = &["item.ron"];
+    const NAME: &'static str = "item";
+
+    fn from_definitions(items: impl IntoIterator<Item = Item>) -> Result<Self, ItemRegistryError> {
+        Self::new(items)
+    }
+
+    fn count(&self) -> usize {
+        self.items.len()
+    }
+
+    fn checksum(&self) -> u64 {
+        definitions_checksum(&self.items.values().collect::<Vec<_>>())
+    }
+}
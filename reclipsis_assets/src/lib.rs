@@ -1,4 +1,11 @@
 pub mod block;
 pub mod character;
+pub mod definitions;
 pub mod floor;
+pub mod health;
 pub mod inventory;
+pub mod item;
+pub mod projectile;
+pub mod spawn;
+pub mod voxel;
+pub mod world_item;
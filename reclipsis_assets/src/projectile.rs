@@ -0,0 +1,35 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::ItemId;
+
+pub const PROJECTILE_RADIUS: f32 = 0.1;
+
+/// Shot by a ranged item, its damage and lifetime come from the item
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Projectile {
+    pub item: ItemId,
+}
+
+#[derive(Bundle)]
+pub struct ProjectilePhysicsBundle {
+    collider: Collider,
+    rigid_body: RigidBody,
+    gravity_scale: GravityScale,
+    // Fast enough to tunnel through characters between ticks otherwise
+    ccd: SweptCcd,
+    collision_events: CollisionEventsEnabled,
+}
+
+impl ProjectilePhysicsBundle {
+    pub fn new(gravity_scale: f32) -> Self {
+        Self {
+            collider: Collider::sphere(PROJECTILE_RADIUS),
+            rigid_body: RigidBody::Dynamic,
+            gravity_scale: GravityScale(gravity_scale),
+            ccd: SweptCcd::default(),
+            collision_events: CollisionEventsEnabled,
+        }
+    }
+}
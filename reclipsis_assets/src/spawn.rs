@@ -0,0 +1,8 @@
+use bevy::prelude::*;
+
+// Characters below this height have fallen off the world
+pub const KILL_PLANE_HEIGHT: f32 = -50.0;
+
+/// Level entity marking where characters can spawn, placed with its `Transform`
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct SpawnPoint;
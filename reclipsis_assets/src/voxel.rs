@@ -0,0 +1,409 @@
+use std::collections::{HashMap, HashSet};
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::block::{self, BlockMaterialBundle, BlockType, CELL_SIZE};
+
+/// Chunks are cubes of this many cells on each side
+pub const CHUNK_SIZE: i32 = 16;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Kind of block filling a cell of the building grid
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct BlockId(pub u16);
+
+impl BlockId {
+    pub const AIR: Self = Self(0);
+
+    pub fn is_air(self) -> bool {
+        self == Self::AIR
+    }
+}
+
+/// Chunk containing `cell` and the index of the cell within it
+pub fn split_cell(cell: IVec3) -> (IVec3, u16) {
+    let chunk = cell.div_euclid(IVec3::splat(CHUNK_SIZE));
+    let local = cell.rem_euclid(IVec3::splat(CHUNK_SIZE));
+    (chunk, local_index(local) as u16)
+}
+
+/// Inverse of [`split_cell`], `None` for indices outside of a chunk
+pub fn chunk_cell(chunk: IVec3, index: u16) -> Option<IVec3> {
+    let index = index as usize;
+    (index < CHUNK_VOLUME).then(|| chunk * CHUNK_SIZE + local_cell(index))
+}
+
+fn local_index(local: IVec3) -> usize {
+    (local.x + local.y * CHUNK_SIZE + local.z * CHUNK_SIZE * CHUNK_SIZE) as usize
+}
+
+fn local_cell(index: usize) -> IVec3 {
+    let index = index as i32;
+    IVec3::new(
+        index % CHUNK_SIZE,
+        index / CHUNK_SIZE % CHUNK_SIZE,
+        index / (CHUNK_SIZE * CHUNK_SIZE),
+    )
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    blocks: Vec<BlockId>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self {
+            blocks: vec![BlockId::AIR; CHUNK_VOLUME],
+        }
+    }
+}
+
+/// Run-length encoded chunk, chunks are mostly air so the runs are long
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompressedChunk(Vec<(BlockId, u16)>);
+
+/// Box of identical blocks, in cells relative to the chunk's first cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MergedBox {
+    pub min: IVec3,
+    pub size: IVec3,
+    pub block: BlockId,
+}
+
+impl Chunk {
+    pub fn get(&self, index: u16) -> BlockId {
+        self.blocks
+            .get(index as usize)
+            .copied()
+            .unwrap_or(BlockId::AIR)
+    }
+
+    /// Returns the block that was there before
+    pub fn set(&mut self, index: u16, block: BlockId) -> BlockId {
+        self.blocks
+            .get_mut(index as usize)
+            .map_or(BlockId::AIR, |cell| std::mem::replace(cell, block))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|block| block.is_air())
+    }
+
+    pub fn compress(&self) -> CompressedChunk {
+        let mut runs: Vec<(BlockId, u16)> = Vec::new();
+
+        for &block in &self.blocks {
+            match runs.last_mut() {
+                Some((run_block, count)) if *run_block == block => *count += 1,
+                _ => runs.push((block, 1)),
+            }
+        }
+
+        CompressedChunk(runs)
+    }
+
+    /// Returns `None` if the runs don't add up to a whole chunk
+    pub fn decompress(compressed: &CompressedChunk) -> Option<Self> {
+        let mut blocks = Vec::with_capacity(CHUNK_VOLUME);
+        for &(block, count) in &compressed.0 {
+            if blocks.len() + count as usize > CHUNK_VOLUME {
+                return None;
+            }
+            blocks.extend(std::iter::repeat_n(block, count as usize));
+        }
+
+        (blocks.len() == CHUNK_VOLUME).then_some(Self { blocks })
+    }
+
+    /// Greedily merges runs of identical blocks into boxes, first along x, then
+    /// z, then y. Only depends on the blocks, so every peer builds the same boxes
+    pub fn merged_boxes(&self) -> Vec<MergedBox> {
+        let mut merged = vec![false; CHUNK_VOLUME];
+        let mut boxes = Vec::new();
+
+        let fits = |merged: &[bool], min: IVec3, size: IVec3, block: BlockId| {
+            (min.y..min.y + size.y).all(|y| {
+                (min.z..min.z + size.z).all(|z| {
+                    (min.x..min.x + size.x).all(|x| {
+                        let index = local_index(IVec3::new(x, y, z));
+                        !merged[index] && self.blocks[index] == block
+                    })
+                })
+            })
+        };
+
+        for index in 0..CHUNK_VOLUME {
+            let block = self.blocks[index];
+            if block.is_air() || merged[index] {
+                continue;
+            }
+
+            let min = local_cell(index);
+            let mut size = IVec3::ONE;
+            while min.x + size.x < CHUNK_SIZE
+                && fits(&merged, min + IVec3::X * size.x, IVec3::new(1, 1, 1), block)
+            {
+                size.x += 1;
+            }
+            while min.z + size.z < CHUNK_SIZE
+                && fits(
+                    &merged,
+                    min + IVec3::Z * size.z,
+                    IVec3::new(size.x, 1, 1),
+                    block,
+                )
+            {
+                size.z += 1;
+            }
+            while min.y + size.y < CHUNK_SIZE
+                && fits(
+                    &merged,
+                    min + IVec3::Y * size.y,
+                    IVec3::new(size.x, 1, size.z),
+                    block,
+                )
+            {
+                size.y += 1;
+            }
+
+            for y in min.y..min.y + size.y {
+                for z in min.z..min.z + size.z {
+                    for x in min.x..min.x + size.x {
+                        merged[local_index(IVec3::new(x, y, z))] = true;
+                    }
+                }
+            }
+            boxes.push(MergedBox { min, size, block });
+        }
+
+        boxes
+    }
+}
+
+/// Blocks placed on the building grid, stored in chunks instead of one entity
+/// per block. Free-moving blocks are still entities, see `block`
+#[derive(Component, Default, Debug)]
+pub struct VoxelWorld {
+    chunks: HashMap<IVec3, Chunk>,
+    // Chunks whose colliders and meshes are out of date
+    dirty: HashSet<IVec3>,
+}
+
+impl VoxelWorld {
+    pub fn get(&self, cell: IVec3) -> BlockId {
+        let (chunk, index) = split_cell(cell);
+        self.chunks
+            .get(&chunk)
+            .map_or(BlockId::AIR, |chunk| chunk.get(index))
+    }
+
+    /// Returns the block that was there before
+    pub fn set(&mut self, cell: IVec3, block: BlockId) -> BlockId {
+        let (chunk_pos, index) = split_cell(cell);
+
+        let previous = match self.chunks.get_mut(&chunk_pos) {
+            Some(chunk) => chunk.set(index, block),
+            None if block.is_air() => return BlockId::AIR,
+            None => self.chunks.entry(chunk_pos).or_default().set(index, block),
+        };
+
+        if previous != block {
+            self.dirty.insert(chunk_pos);
+            if self.chunks[&chunk_pos].is_empty() {
+                self.chunks.remove(&chunk_pos);
+            }
+        }
+        previous
+    }
+
+    pub fn chunk(&self, pos: IVec3) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = (IVec3, &Chunk)> {
+        self.chunks.iter().map(|(pos, chunk)| (*pos, chunk))
+    }
+
+    pub fn insert_chunk(&mut self, pos: IVec3, chunk: Chunk) {
+        if chunk.is_empty() {
+            self.chunks.remove(&pos);
+        } else {
+            self.chunks.insert(pos, chunk);
+        }
+        self.dirty.insert(pos);
+    }
+
+    pub fn clear(&mut self) {
+        self.dirty.extend(self.chunks.keys().copied());
+        self.chunks.clear();
+    }
+
+    /// Chunks changed since the last call
+    pub fn take_dirty(&mut self) -> Vec<IVec3> {
+        self.dirty.drain().collect()
+    }
+}
+
+/// Collider entity for the `block` cells of a chunk of `world`, block types
+/// differ in their surface properties so each gets its own
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct VoxelChunk {
+    pub world: Entity,
+    pub pos: IVec3,
+    pub block: BlockId,
+}
+
+impl VoxelChunk {
+    /// Center of the chunk's first cell, merged boxes are relative to it
+    pub fn origin(&self) -> Vec3 {
+        block::cell_center(self.pos * CHUNK_SIZE)
+    }
+}
+
+impl MergedBox {
+    /// Center of the box relative to the chunk's origin
+    pub fn center(&self) -> Vec3 {
+        (self.min.as_vec3() + (self.size.as_vec3() - 1.0) * 0.5) * CELL_SIZE
+    }
+
+    pub fn extents(&self) -> Vec3 {
+        self.size.as_vec3() * CELL_SIZE
+    }
+}
+
+#[derive(Bundle)]
+pub struct ChunkPhysicsBundle {
+    collider: Collider,
+    rigid_body: RigidBody,
+    material: BlockMaterialBundle,
+}
+
+impl ChunkPhysicsBundle {
+    /// One cuboid per merged box of `block_type`, `None` if there are none
+    pub fn new(boxes: &[MergedBox], block_type: &BlockType) -> Option<Self> {
+        let shapes: Vec<_> = boxes
+            .iter()
+            .filter(|merged| merged.block == block_type.id)
+            .map(|merged| {
+                let extents = merged.extents();
+                (
+                    merged.center(),
+                    Quat::IDENTITY,
+                    Collider::cuboid(extents.x, extents.y, extents.z),
+                )
+            })
+            .collect();
+        if shapes.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            collider: Collider::compound(shapes),
+            rigid_body: RigidBody::Static,
+            material: BlockMaterialBundle::new(block_type),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: BlockId = BlockId(1);
+    const ICE: BlockId = BlockId(2);
+
+    #[test]
+    fn cells_split_into_chunks_below_zero() {
+        for cell in [
+            IVec3::ZERO,
+            IVec3::new(15, 0, 16),
+            IVec3::new(-1, -1, -1),
+            IVec3::new(-17, 3, 40),
+        ] {
+            let (chunk, index) = split_cell(cell);
+            assert_eq!(chunk_cell(chunk, index), Some(cell));
+        }
+
+        assert_eq!(split_cell(IVec3::new(-1, 0, 0)).0, IVec3::new(-1, 0, 0));
+    }
+
+    #[test]
+    fn indices_outside_of_a_chunk_have_no_cell() {
+        let last = CHUNK_VOLUME as u16 - 1;
+
+        assert_eq!(
+            chunk_cell(IVec3::ONE, last),
+            Some(IVec3::splat(2 * CHUNK_SIZE - 1))
+        );
+        assert_eq!(chunk_cell(IVec3::ONE, last + 1), None);
+        assert_eq!(chunk_cell(IVec3::ZERO, u16::MAX), None);
+    }
+
+    #[test]
+    fn compression_round_trips() {
+        let mut chunk = Chunk::default();
+        assert_eq!(
+            chunk.compress().0,
+            vec![(BlockId::AIR, CHUNK_VOLUME as u16)]
+        );
+
+        chunk.set(0, STONE);
+        chunk.set(1, STONE);
+        chunk.set(700, ICE);
+        let compressed = chunk.compress();
+        assert_eq!(compressed.0.len(), 4);
+        assert_eq!(Chunk::decompress(&compressed), Some(chunk));
+
+        assert_eq!(Chunk::decompress(&CompressedChunk(vec![(STONE, 5)])), None);
+    }
+
+    #[test]
+    fn merged_boxes_cover_each_block_once() {
+        let mut world = VoxelWorld::default();
+        // A 4x2x3 slab of stone with an ice block on top
+        for x in 0..4 {
+            for y in 0..2 {
+                for z in 0..3 {
+                    world.set(IVec3::new(x, y, z), STONE);
+                }
+            }
+        }
+        world.set(IVec3::new(1, 2, 1), ICE);
+
+        let boxes = world.chunk(IVec3::ZERO).unwrap().merged_boxes();
+        assert_eq!(
+            boxes,
+            vec![
+                MergedBox {
+                    min: IVec3::ZERO,
+                    size: IVec3::new(4, 2, 3),
+                    block: STONE,
+                },
+                MergedBox {
+                    min: IVec3::new(1, 2, 1),
+                    size: IVec3::ONE,
+                    block: ICE,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn emptied_chunks_are_dropped() {
+        let mut world = VoxelWorld::default();
+        let cell = IVec3::new(-3, 20, 5);
+
+        assert_eq!(world.set(cell, STONE), BlockId::AIR);
+        assert_eq!(world.get(cell), STONE);
+        assert_eq!(world.take_dirty(), vec![split_cell(cell).0]);
+
+        assert_eq!(world.set(cell, BlockId::AIR), STONE);
+        assert_eq!(world.chunks().count(), 0);
+        assert_eq!(world.take_dirty().len(), 1);
+    }
+}
@@ -0,0 +1,33 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::ItemStack;
+
+pub const WORLD_ITEM_SIZE: f32 = 0.4;
+
+/// Item stack lying in the world, waiting to be picked up
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WorldItem {
+    pub stack: ItemStack,
+}
+
+/// Ticks left before a dropped item can be picked up, so it isn't walked right
+/// back into the inventory it was dropped from
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PickupDelay(pub u16);
+
+#[derive(Bundle)]
+pub struct WorldItemPhysicsBundle {
+    collider: Collider,
+    rigid_body: RigidBody,
+}
+
+impl Default for WorldItemPhysicsBundle {
+    fn default() -> Self {
+        Self {
+            collider: Collider::cuboid(WORLD_ITEM_SIZE, WORLD_ITEM_SIZE, WORLD_ITEM_SIZE),
+            rigid_body: RigidBody::Dynamic,
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use avian3d::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+use leafwing_input_manager::prelude::*;
+use lightyear::prelude::*;
+use reclipsis_assets::{
+    block::{self, BlockMarker},
+    character::CharacterMarker,
+    health::Dead,
+    inventory::{EquippedSlot, Inventory, ItemStack},
+    item::{ItemKind, ItemRegistry},
+    voxel::{BlockId, VoxelChunk, VoxelWorld},
+};
+use reclipsis_common::{
+    building::{self, BuildRejection},
+    protocol::*,
+};
+
+use crate::{
+    AppState,
+    game::{BlockVisuals, SpawnedState, voxel::BuildingAllowed},
+};
+
+// The camera orbits up to 40 away from the character
+const MAX_RAY_DISTANCE: f32 = 50.0;
+
+const GHOST_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.3);
+
+pub struct BuildingPlugin;
+
+impl Plugin for BuildingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BuildTarget>()
+            .add_systems(OnEnter(AppState::Game), spawn_ghost)
+            .add_systems(
+                Update,
+                (update_build_target, update_ghost)
+                    .chain()
+                    .run_if(in_state(SpawnedState::Spawned)),
+            )
+            .add_systems(
+                FixedUpdate,
+                send_build_requests
+                    .after(super::handle_character_actions)
+                    .run_if(in_state(SpawnedState::Spawned).and(resource_exists::<ItemRegistry>)),
+            );
+    }
+}
+
+/// What the camera ray points at while a block item is equipped
+#[derive(Resource, Default, Debug)]
+struct BuildTarget {
+    slot: Option<usize>,
+    place: Option<IVec3>,
+    remove: Option<IVec3>,
+    // Rollbacks replay ticks whose presses were already sent
+    last_sent_tick: Option<Tick>,
+}
+
+/// Translucent preview of the block about to be placed
+#[derive(Component)]
+struct BuildGhost;
+
+/// Applies requests to the predicted inventory and the world right away and sends
+/// them to the server, whose inventory and world edits replace the prediction
+#[derive(SystemParam)]
+struct BuildRequests<'w, 's> {
+    registry: Res<'w, ItemRegistry>,
+    client: Single<
+        'w,
+        (
+            &'static mut MessageSender<BuildRequest>,
+            &'static mut VoxelWorld,
+            &'static BuildingAllowed,
+        ),
+        With<Client>,
+    >,
+    character: Single<
+        'w,
+        (&'static mut Inventory, &'static Position, Has<Dead>),
+        (With<CharacterMarker>, With<Controlled>, With<Predicted>),
+    >,
+    spatial_query: SpatialQuery<'w, 's>,
+    bodies: Query<'w, 's, (), Or<(With<CharacterMarker>, With<BlockMarker>)>>,
+}
+
+impl BuildRequests<'_, '_> {
+    // Predicted with the server's checks, without its reach tolerance, so the
+    // server doesn't reject what was predicted
+    fn place(&mut self, cell: IVec3, slot: usize) {
+        if let Err(rejection) = self.try_place(cell, slot) {
+            debug!("Not placing a block in {cell}: {rejection:?}");
+        }
+    }
+
+    fn try_place(&mut self, cell: IVec3, slot: usize) -> Result<(), BuildRejection> {
+        let (sender, world, building_allowed) = &mut *self.client;
+        let (inventory, position, dead) = &mut *self.character;
+        building::check_access(*dead, building_allowed.0, position.0, cell, 0.0)?;
+
+        let Some(ItemKind::Block { block }) = inventory
+            .get(slot)
+            .and_then(|stack| self.registry.get(stack.id))
+            .map(|item| &item.kind)
+        else {
+            return Err(BuildRejection::NotPlaceable);
+        };
+        if building::is_occupied(
+            world,
+            &self.spatial_query,
+            &SpatialQueryFilter::default(),
+            cell,
+            |entity| self.bodies.contains(entity),
+        ) {
+            return Err(BuildRejection::Occupied);
+        }
+        inventory
+            .remove(slot, 1)
+            .map_err(BuildRejection::Inventory)?;
+
+        world.set(cell, *block);
+        sender.send::<ReliableChannel>(BuildRequest::Place { cell, slot });
+        Ok(())
+    }
+
+    fn remove(&mut self, cell: IVec3) {
+        if let Err(rejection) = self.try_remove(cell) {
+            debug!("Not removing the block in {cell}: {rejection:?}");
+        }
+    }
+
+    fn try_remove(&mut self, cell: IVec3) -> Result<(), BuildRejection> {
+        let (sender, world, building_allowed) = &mut *self.client;
+        let (inventory, position, dead) = &mut *self.character;
+        building::check_access(*dead, building_allowed.0, position.0, cell, 0.0)?;
+
+        let item = self
+            .registry
+            .block_item(world.get(cell))
+            .ok_or(BuildRejection::NoBlock)?;
+        inventory
+            .add(&self.registry, ItemStack::new(item, 1))
+            .map_err(BuildRejection::Inventory)?;
+
+        world.set(cell, BlockId::AIR);
+        sender.send::<ReliableChannel>(BuildRequest::Remove { cell });
+        Ok(())
+    }
+}
+
+fn spawn_ghost(
+    mut commands: Commands,
+    block_visuals: Res<BlockVisuals>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        Name::new("Build Ghost"),
+        BuildGhost,
+        Mesh3d(block_visuals.mesh.clone()),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: GHOST_COLOR,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        })),
+        Transform::default(),
+        Visibility::Hidden,
+        StateScoped(AppState::Game),
+    ));
+}
+
+fn update_build_target(
+    mut target: ResMut<BuildTarget>,
+    registry: Option<Res<ItemRegistry>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    character: Single<
+        (Entity, &Position, &Inventory, &EquippedSlot),
+        (With<CharacterMarker>, With<Controlled>, With<Predicted>),
+    >,
+    world: Single<&VoxelWorld, With<Client>>,
+    chunks: Query<(), With<VoxelChunk>>,
+    spatial_query: SpatialQuery,
+) {
+    target.slot = None;
+    target.place = None;
+    target.remove = None;
+
+    let (entity, position, inventory, equipped_slot) = character.into_inner();
+    let holds_block = equipped_slot
+        .stack(inventory)
+        .and_then(|stack| registry.as_ref()?.get(stack.id))
+        .is_some_and(|item| matches!(item.kind, ItemKind::Block { .. }));
+    if !holds_block {
+        return;
+    }
+
+    let (camera, camera_transform) = *camera;
+    let Some(ray) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+    let Some(hit) = spatial_query.cast_ray(
+        ray.origin,
+        ray.direction,
+        MAX_RAY_DISTANCE,
+        true,
+        &SpatialQueryFilter::default().with_excluded_entities(vec![entity]),
+    ) else {
+        return;
+    };
+
+    // Half a block along the normal lands in the cells on either side of the hit face
+    let point = ray.get_point(hit.distance);
+    let offset = hit.normal * block::BLOCK_WIDTH * 0.5;
+    let place = block::cell_at(point + offset);
+    let remove = block::cell_at(point - offset);
+
+    target.slot = equipped_slot.0;
+    target.place = building::in_reach(position.0, place, 0.0).then_some(place);
+    // Only blocks on the grid can be removed, free-moving ones are entities
+    target.remove = (chunks.contains(hit.entity)
+        && !world.get(remove).is_air()
+        && building::in_reach(position.0, remove, 0.0))
+    .then_some(remove);
+}
+
+fn update_ghost(
+    target: Res<BuildTarget>,
+    ghost: Single<(&mut Transform, &mut Visibility), With<BuildGhost>>,
+) {
+    let (mut transform, mut visibility) = ghost.into_inner();
+
+    match target.place {
+        Some(cell) => {
+            transform.translation = block::cell_center(cell);
+            *visibility = Visibility::Visible;
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
+
+// Runs with the simulation so presses line up with the other inputs, the target
+// comes from the last frame's camera ray
+fn send_build_requests(
+    mut target: ResMut<BuildTarget>,
+    mut requests: BuildRequests,
+    timeline: Single<&LocalTimeline>,
+    action_state: Single<
+        &ActionState<CharacterAction>,
+        (With<CharacterMarker>, With<Controlled>, With<Predicted>),
+    >,
+) {
+    let tick = timeline.tick();
+    if target.last_sent_tick.is_some_and(|last| tick - last <= 0) {
+        return;
+    }
+    target.last_sent_tick = Some(tick);
+
+    if action_state.just_pressed(&CharacterAction::UsePrimary)
+        && let (Some(slot), Some(cell)) = (target.slot, target.place)
+    {
+        requests.place(cell, slot);
+    }
+
+    if action_state.just_pressed(&CharacterAction::UseSecondary)
+        && let Some(cell) = target.remove
+    {
+        requests.remove(cell);
+    }
+}
@@ -6,14 +6,17 @@ use bevy::{
     window::PrimaryWindow,
 };
 use leafwing_input_manager::{plugin::InputManagerSystem, prelude::ActionState};
-use reclipsis_common::protocol::CharacterAction;
+use reclipsis_common::{protocol::CharacterAction, rotate_value};
 use std::{f32::consts::FRAC_PI_2, ops::Range};
 
 use lightyear::{input::client::InputSet, prelude::Controlled};
-use reclipsis_assets::character::CharacterMarker;
+use reclipsis_assets::{character::CharacterMarker, health::Dead};
 
 use crate::game::SpawnedState;
 
+/// Held to orbit and zoom, the other buttons use items
+pub const ORBIT_BUTTON: MouseButton = MouseButton::Middle;
+
 const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
 
 const SCROLL_SENSITIVITY: f32 = 1.0;
@@ -28,7 +31,14 @@ impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CameraYaw>()
             .init_resource::<OrbitDistance>()
-            .add_systems(Update, orbit.run_if(in_state(SpawnedState::Spawned)))
+            .add_systems(
+                Update,
+                (
+                    face_spawn_rotation,
+                    orbit.run_if(in_state(SpawnedState::Spawned)),
+                )
+                    .chain(),
+            )
             .add_systems(
                 FixedPreUpdate,
                 update_character_rotation
@@ -72,14 +82,19 @@ pub fn orbit(
     spatial_query: SpatialQuery,
 ) {
     let motion_delta = mouse_motion.delta;
-    let scroll_delta = -mouse_scroll.delta.y * SCROLL_SENSITIVITY;
+    // Otherwise the wheel cycles through the hotbar
+    let scroll_delta = if mouse.pressed(ORBIT_BUTTON) {
+        -mouse_scroll.delta.y * SCROLL_SENSITIVITY
+    } else {
+        0.0
+    };
 
     orbit_distance.0 = (orbit_distance.0 + scroll_delta).clamp(ORBIT_RANGE.start, ORBIT_RANGE.end);
 
     let mut delta_pitch = motion_delta.y * PITCH_SPEED;
     let mut delta_yaw = motion_delta.x * YAW_SPEED;
 
-    if !mouse.pressed(MouseButton::Right) {
+    if !mouse.pressed(ORBIT_BUTTON) {
         // Unlock cursor
         for mut window in windows.iter_mut() {
             window.cursor_options.grab_mode = bevy::window::CursorGrabMode::None;
@@ -122,6 +137,26 @@ pub fn orbit(
     }
 }
 
+// Spawn points face the level center, but the rotate input would turn the
+// character back on the next tick unless it starts out from there too
+fn face_spawn_rotation(
+    character: Single<(Entity, &Rotation, Ref<Controlled>), With<CharacterMarker>>,
+    mut respawned: RemovedComponents<Dead>,
+    mut camera: Single<&mut Transform, With<Camera>>,
+    mut camera_yaw: ResMut<CameraYaw>,
+) {
+    let (entity, rotation, controlled) = character.into_inner();
+    let respawned = respawned.read().any(|removed| removed == entity);
+    if !controlled.is_added() && !respawned {
+        return;
+    }
+
+    let yaw = rotate_value(rotation.0);
+    let (_, pitch, _) = camera.rotation.to_euler(EulerRot::YXZ);
+    camera.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0);
+    camera_yaw.0 = Some(yaw);
+}
+
 fn update_character_rotation(
     mut character_action_state: Single<
         &mut ActionState<CharacterAction>,
@@ -0,0 +1,149 @@
+use bevy::{
+    input::{
+        ButtonState,
+        keyboard::{Key, KeyboardInput},
+    },
+    prelude::*,
+};
+use leafwing_input_manager::prelude::ActionState;
+use lightyear::prelude::*;
+use reclipsis_assets::character::CharacterMarker;
+use reclipsis_common::protocol::*;
+
+use crate::AppState;
+
+const MAX_LOG_LINES: usize = 8;
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatState>()
+            .add_systems(OnEnter(AppState::Game), spawn_chat)
+            .add_systems(
+                Update,
+                (type_chat, receive_chat, update_chat_text)
+                    .chain()
+                    .run_if(in_state(AppState::Game)),
+            );
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct ChatState {
+    open: bool,
+    input: String,
+    log: Vec<String>,
+}
+
+/// Run condition for keyboard shortcuts that shouldn't fire while typing
+pub fn is_typing(chat: Res<ChatState>) -> bool {
+    chat.open
+}
+
+#[derive(Component)]
+struct ChatLogText;
+
+#[derive(Component)]
+struct ChatInputText;
+
+fn spawn_chat(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("Chat"),
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            StateScoped(AppState::Game),
+        ))
+        .with_children(|parent| {
+            parent.spawn((Text::default(), ChatLogText));
+            parent.spawn((Text::default(), ChatInputText));
+        });
+}
+
+fn type_chat(
+    mut chat: ResMut<ChatState>,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut sender: Single<&mut MessageSender<ChatMessage>, With<Client>>,
+    mut character_action_state: Query<
+        &mut ActionState<CharacterAction>,
+        (With<CharacterMarker>, With<Controlled>),
+    >,
+) {
+    let was_open = chat.open;
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        match (&event.logical_key, chat.open) {
+            (Key::Enter, false) => chat.open = true,
+            (Key::Enter, true) => {
+                let text = std::mem::take(&mut chat.input);
+                if !text.trim().is_empty() {
+                    sender.send::<ReliableChannel>(ChatMessage { text });
+                }
+                chat.open = false;
+            }
+            (Key::Escape, true) => {
+                chat.input.clear();
+                chat.open = false;
+            }
+            (Key::Backspace, true) => {
+                chat.input.pop();
+            }
+            (Key::Space, true) => chat.input.push(' '),
+            (Key::Character(characters), true) => chat.input.push_str(characters),
+            _ => {}
+        }
+    }
+
+    // Typing must not move the character
+    if chat.open != was_open {
+        for mut action_state in &mut character_action_state {
+            if chat.open {
+                action_state.disable_all();
+            } else {
+                action_state.enable_all();
+            }
+        }
+    }
+}
+
+fn receive_chat(
+    mut chat: ResMut<ChatState>,
+    mut receiver: Single<&mut MessageReceiver<ChatBroadcast>, With<Client>>,
+) {
+    for broadcast in receiver.receive() {
+        chat.log
+            .push(format!("{}: {}", broadcast.sender, broadcast.text));
+    }
+
+    let overflow = chat.log.len().saturating_sub(MAX_LOG_LINES);
+    if overflow > 0 {
+        chat.log.drain(..overflow);
+    }
+}
+
+fn update_chat_text(
+    chat: Res<ChatState>,
+    mut log_text: Single<&mut Text, (With<ChatLogText>, Without<ChatInputText>)>,
+    mut input_text: Single<&mut Text, (With<ChatInputText>, Without<ChatLogText>)>,
+) {
+    if !chat.is_changed() {
+        return;
+    }
+
+    log_text.0 = chat.log.join("\n");
+    input_text.0 = if chat.open {
+        format!("> {}", chat.input)
+    } else {
+        String::new()
+    };
+}
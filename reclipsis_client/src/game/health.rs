@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use lightyear::prelude::Controlled;
+use reclipsis_assets::{
+    character::CharacterMarker,
+    health::{Dead, Health},
+};
+
+use crate::{AppState, game::SpawnedState};
+
+const HEALTH_BAR_WIDTH: f32 = 200.0;
+const HEALTH_BAR_HEIGHT: f32 = 16.0;
+
+const HEALTH_COLOR: Color = Color::srgb(0.8, 0.1, 0.1);
+const DEATH_SCREEN_BACKGROUND: Color = Color::srgba(0.3, 0.0, 0.0, 0.6);
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), spawn_health_bar)
+            .add_systems(
+                Update,
+                (update_health_bar, update_death_screen).run_if(in_state(SpawnedState::Spawned)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct HealthBarFill;
+
+#[derive(Component)]
+struct HealthBarText;
+
+#[derive(Component)]
+struct DeathScreen {
+    // Elapsed seconds
+    respawn_at: f32,
+}
+
+#[derive(Component)]
+struct RespawnCountdown;
+
+fn spawn_health_bar(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("Health Bar"),
+            StateScoped(AppState::Game),
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(84.0),
+                left: Val::Percent(50.0),
+                margin: UiRect::left(Val::Px(-HEALTH_BAR_WIDTH / 2.0)),
+                width: Val::Px(HEALTH_BAR_WIDTH),
+                height: Val::Px(HEALTH_BAR_HEIGHT),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                HealthBarFill,
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(HEALTH_COLOR),
+            ));
+            parent.spawn((
+                HealthBarText,
+                Text::default(),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn update_health_bar(
+    health: Single<Ref<Health>, (With<CharacterMarker>, With<Controlled>)>,
+    mut fill: Single<&mut Node, With<HealthBarFill>>,
+    mut text: Single<&mut Text, With<HealthBarText>>,
+) {
+    if !health.is_changed() {
+        return;
+    }
+
+    fill.width = Val::Percent(health.fraction() * 100.0);
+    text.0 = format!("{:.0} / {:.0}", health.current.ceil(), health.max);
+}
+
+fn update_death_screen(
+    mut commands: Commands,
+    time: Res<Time>,
+    dead: Single<Option<&Dead>, (With<CharacterMarker>, With<Controlled>)>,
+    death_screen: Option<Single<(Entity, &DeathScreen)>>,
+    mut countdown: Query<&mut Text, With<RespawnCountdown>>,
+) {
+    match (*dead, death_screen) {
+        (Some(dead), None) => spawn_death_screen(&mut commands, dead, time.elapsed_secs()),
+        (Some(_), Some(death_screen)) => {
+            let remaining = (death_screen.1.respawn_at - time.elapsed_secs()).max(0.0);
+            for mut text in &mut countdown {
+                text.0 = format!("Respawning in {}", remaining.ceil());
+            }
+        }
+        (None, Some(death_screen)) => commands.entity(death_screen.0).despawn(),
+        (None, None) => {}
+    }
+}
+
+fn spawn_death_screen(commands: &mut Commands, dead: &Dead, now: f32) {
+    commands
+        .spawn((
+            Name::new("Death Screen"),
+            DeathScreen {
+                respawn_at: now + dead.respawn_delay_secs,
+            },
+            StateScoped(AppState::Game),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(DEATH_SCREEN_BACKGROUND),
+            Pickable::IGNORE,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("You died"),
+                TextFont {
+                    font_size: 48.0,
+                    ..default()
+                },
+            ));
+            parent.spawn(Text::new(format!("Killed by {}", dead.cause)));
+            parent.spawn((RespawnCountdown, Text::default()));
+        });
+}
@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+use lightyear::prelude::Controlled;
+use reclipsis_assets::{
+    character::CharacterMarker,
+    inventory::{EquippedSlot, HOTBAR_SLOTS, Inventory},
+    item::ItemRegistry,
+};
+
+use crate::{AppState, game::SpawnedState};
+
+pub const SLOT_SIZE: f32 = 64.0;
+const ICON_SIZE: f32 = 32.0;
+
+const SLOT_BORDER: Color = Color::srgba(1.0, 1.0, 1.0, 0.2);
+pub const SLOT_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.5);
+const EQUIPPED_SLOT_BORDER: Color = Color::srgb(1.0, 0.8, 0.2);
+
+pub struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), spawn_hotbar)
+            .add_systems(
+                Update,
+                update_item_slots
+                    .run_if(in_state(SpawnedState::Spawned).and(resource_exists::<ItemRegistry>)),
+            );
+    }
+}
+
+/// Index into the inventory
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ItemSlot(pub usize);
+
+#[derive(Component)]
+struct ItemSlotIcon(usize);
+
+#[derive(Component)]
+struct ItemSlotName(usize);
+
+#[derive(Component)]
+struct ItemSlotCount(usize);
+
+fn spawn_hotbar(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("Hotbar"),
+            StateScoped(AppState::Game),
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(4.0),
+                ..default()
+            },
+            // Spans the screen width, only the slots count as being over the UI
+            Pickable::IGNORE,
+        ))
+        .with_children(|parent| {
+            for slot in 0..HOTBAR_SLOTS {
+                spawn_item_slot(parent, slot);
+            }
+        });
+}
+
+/// Slot showing the contents of an inventory slot, used by the hotbar and the
+/// inventory window alike
+pub fn spawn_item_slot(parent: &mut ChildSpawnerCommands, slot: usize) {
+    parent
+        .spawn((
+            ItemSlot(slot),
+            Node {
+                width: Val::Px(SLOT_SIZE),
+                height: Val::Px(SLOT_SIZE),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceBetween,
+                border: UiRect::all(Val::Px(2.0)),
+                ..default()
+            },
+            BorderColor(SLOT_BORDER),
+            BackgroundColor(SLOT_BACKGROUND),
+        ))
+        // Drags and drops should hit the slot, not its contents
+        .with_children(|parent| {
+            if slot < HOTBAR_SLOTS {
+                parent.spawn((
+                    Text::new((slot + 1).to_string()),
+                    TextFont {
+                        font_size: 10.0,
+                        ..default()
+                    },
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(2.0),
+                        top: Val::Px(0.0),
+                        ..default()
+                    },
+                    Pickable::IGNORE,
+                ));
+            }
+            parent.spawn((
+                ItemSlotIcon(slot),
+                ImageNode::default(),
+                Node {
+                    width: Val::Px(ICON_SIZE),
+                    height: Val::Px(ICON_SIZE),
+                    margin: UiRect::top(Val::Px(6.0)),
+                    ..default()
+                },
+                Visibility::Hidden,
+                Pickable::IGNORE,
+            ));
+            parent.spawn((
+                ItemSlotName(slot),
+                Text::default(),
+                TextFont {
+                    font_size: 9.0,
+                    ..default()
+                },
+                Pickable::IGNORE,
+            ));
+            parent.spawn((
+                ItemSlotCount(slot),
+                Text::default(),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+                Node {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(3.0),
+                    top: Val::Px(0.0),
+                    ..default()
+                },
+                Pickable::IGNORE,
+            ));
+        });
+}
+
+// The inventory is replaced on every server update and rollback, so this only
+// touches the UI when it actually changed
+fn update_item_slots(
+    character: Single<
+        (Ref<Inventory>, Ref<EquippedSlot>),
+        (With<CharacterMarker>, With<Controlled>),
+    >,
+    new_slots: Query<(), Added<ItemSlot>>,
+    registry: Res<ItemRegistry>,
+    asset_server: Res<AssetServer>,
+    mut slots: Query<(&ItemSlot, &mut BorderColor)>,
+    mut icons: Query<(&ItemSlotIcon, &mut ImageNode, &mut Visibility)>,
+    mut names: Query<(&ItemSlotName, &mut Text), Without<ItemSlotCount>>,
+    mut counts: Query<(&ItemSlotCount, &mut Text), Without<ItemSlotName>>,
+) {
+    let (inventory, equipped_slot) = &*character;
+    if !inventory.is_changed() && !equipped_slot.is_changed() && new_slots.is_empty() {
+        return;
+    }
+
+    let item = |slot: usize| {
+        let stack = inventory.get(slot)?;
+        Some((stack, registry.get(stack.id)?))
+    };
+
+    for (slot, mut border) in &mut slots {
+        border.0 = if equipped_slot.0 == Some(slot.0) {
+            EQUIPPED_SLOT_BORDER
+        } else {
+            SLOT_BORDER
+        };
+    }
+
+    for (icon, mut image, mut visibility) in &mut icons {
+        match item(icon.0) {
+            Some((_, item)) => {
+                image.image = asset_server.load(&item.icon);
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+
+    for (name, mut text) in &mut names {
+        text.0 = item(name.0)
+            .map(|(_, item)| item.name.clone())
+            .unwrap_or_default();
+    }
+
+    for (count, mut text) in &mut counts {
+        text.0 = match item(count.0) {
+            Some((stack, _)) if stack.count > 1 => stack.count.to_string(),
+            _ => String::new(),
+        };
+    }
+}
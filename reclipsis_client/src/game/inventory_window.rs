@@ -0,0 +1,220 @@
+use bevy::{
+    ecs::system::SystemParam,
+    picking::{hover::HoverMap, pointer::PointerId},
+    prelude::*,
+};
+use lightyear::prelude::*;
+use reclipsis_assets::{
+    character::CharacterMarker,
+    inventory::{HOTBAR_SLOTS, INVENTORY_SLOTS, Inventory},
+    item::ItemRegistry,
+};
+use reclipsis_common::protocol::*;
+
+use crate::{
+    AppState,
+    game::{
+        SpawnedState, chat,
+        hotbar::{ItemSlot, SLOT_BACKGROUND, SLOT_SIZE, spawn_item_slot},
+    },
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::KeyI;
+
+const DRAGGED_SLOT_BACKGROUND: Color = Color::srgba(1.0, 1.0, 1.0, 0.3);
+
+pub struct InventoryWindowPlugin;
+
+impl Plugin for InventoryWindowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            toggle_inventory_window
+                .run_if(in_state(SpawnedState::Spawned).and(not(chat::is_typing))),
+        )
+        // Hotbar slots take part too, so items can be dragged onto them
+        .add_observer(start_drag)
+        .add_observer(drop_on_slot)
+        .add_observer(end_drag)
+        .add_observer(split_on_shift_click);
+    }
+}
+
+#[derive(Component)]
+struct InventoryWindowRoot;
+
+/// The panel itself, dropping items anywhere else throws them out
+#[derive(Component)]
+struct InventoryWindow;
+
+/// Applies requests to the predicted inventory right away and sends them to the
+/// server, whose replicated inventory replaces the prediction
+#[derive(SystemParam)]
+struct InventoryRequests<'w> {
+    registry: Res<'w, ItemRegistry>,
+    sender: Single<'w, &'static mut MessageSender<InventoryRequest>, With<Client>>,
+    inventory: Single<'w, &'static mut Inventory, (With<CharacterMarker>, With<Controlled>)>,
+}
+
+impl InventoryRequests<'_> {
+    fn send(&mut self, request: InventoryRequest) {
+        // The server would reject it just the same
+        if let Err(err) = request.apply(&mut self.inventory, &self.registry) {
+            debug!("Not sending {request:?}: {err}");
+            return;
+        }
+
+        self.sender.send::<ReliableChannel>(request);
+    }
+}
+
+fn toggle_inventory_window(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    windows: Query<Entity, With<InventoryWindowRoot>>,
+) {
+    let open = !windows.is_empty();
+
+    if keys.just_pressed(TOGGLE_KEY) || (open && keys.just_pressed(KeyCode::Escape)) {
+        if open {
+            for window in &windows {
+                commands.entity(window).despawn();
+            }
+        } else {
+            spawn_inventory_window(&mut commands);
+        }
+    }
+}
+
+fn spawn_inventory_window(commands: &mut Commands) {
+    commands
+        .spawn((
+            Name::new("Inventory"),
+            InventoryWindowRoot,
+            StateScoped(AppState::Game),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            Pickable::IGNORE,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    InventoryWindow,
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(12.0)),
+                        row_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.1, 0.1, 0.1, 0.9)),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((Text::new("Inventory"), Pickable::IGNORE));
+                    parent
+                        .spawn(Node {
+                            display: Display::Grid,
+                            grid_template_columns: RepeatedGridTrack::px(
+                                HOTBAR_SLOTS as u16,
+                                SLOT_SIZE,
+                            ),
+                            row_gap: Val::Px(4.0),
+                            column_gap: Val::Px(4.0),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            // Hotbar slots go in the bottom row
+                            for slot in (HOTBAR_SLOTS..INVENTORY_SLOTS).chain(0..HOTBAR_SLOTS) {
+                                spawn_item_slot(parent, slot);
+                            }
+                        });
+                });
+        });
+}
+
+fn start_drag(
+    trigger: Trigger<Pointer<DragStart>>,
+    mut slots: Query<&mut BackgroundColor, With<ItemSlot>>,
+) {
+    if trigger.button != PointerButton::Primary {
+        return;
+    }
+    if let Ok(mut background) = slots.get_mut(trigger.target()) {
+        background.0 = DRAGGED_SLOT_BACKGROUND;
+    }
+}
+
+fn drop_on_slot(
+    trigger: Trigger<Pointer<DragDrop>>,
+    slots: Query<&ItemSlot>,
+    mut requests: InventoryRequests,
+) {
+    if trigger.button != PointerButton::Primary {
+        return;
+    }
+    let (Ok(from), Ok(to)) = (slots.get(trigger.dropped), slots.get(trigger.target())) else {
+        return;
+    };
+
+    requests.send(InventoryRequest::Move {
+        from: from.0,
+        to: to.0,
+    });
+}
+
+fn end_drag(
+    trigger: Trigger<Pointer<DragEnd>>,
+    mut slots: Query<(&ItemSlot, &mut BackgroundColor)>,
+    windows: Query<(), With<InventoryWindow>>,
+    hover_map: Res<HoverMap>,
+    mut requests: InventoryRequests,
+) {
+    if trigger.button != PointerButton::Primary {
+        return;
+    }
+    let Ok((from, mut background)) = slots.get_mut(trigger.target()) else {
+        return;
+    };
+    background.0 = SLOT_BACKGROUND;
+    let from = from.0;
+
+    // Items are only dropped out of the open inventory, not the hotbar alone
+    if windows.is_empty() {
+        return;
+    }
+    let over_inventory = hover_map.get(&PointerId::Mouse).is_some_and(|hits| {
+        hits.keys()
+            .any(|entity| slots.contains(*entity) || windows.contains(*entity))
+    });
+    if over_inventory {
+        return;
+    }
+
+    if let Some(stack) = requests.inventory.get(from) {
+        let count = stack.count;
+        requests.send(InventoryRequest::Drop { slot: from, count });
+    }
+}
+
+fn split_on_shift_click(
+    trigger: Trigger<Pointer<Click>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    slots: Query<&ItemSlot>,
+    mut requests: InventoryRequests,
+) {
+    if trigger.button != PointerButton::Primary
+        || !keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    {
+        return;
+    }
+    let Ok(slot) = slots.get(trigger.target()) else {
+        return;
+    };
+
+    requests.send(InventoryRequest::Split { slot: slot.0 });
+}
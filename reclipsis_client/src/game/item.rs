@@ -1,44 +1,185 @@
-use bevy::{input::keyboard::KeyboardInput, prelude::*};
-use leafwing_input_manager::{plugin::InputManagerSystem, prelude::ActionState};
-use lightyear::{input::client::InputSet, prelude::Controlled};
-use reclipsis_assets::character::CharacterMarker;
+use std::collections::HashMap;
+
+use bevy::{
+    picking::{hover::HoverMap, pointer::PointerId},
+    prelude::*,
+};
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::*};
+use lightyear::{
+    input::client::InputSet,
+    prelude::{Controlled, Predicted},
+};
+use reclipsis_assets::{
+    character::{self, CharacterMarker},
+    inventory::{EquippedSlot, Inventory, ItemId},
+    item::{ItemKind, ItemRegistry},
+};
 use reclipsis_common::protocol::CharacterAction;
 
+use crate::{AppState, game::camera::ORBIT_BUTTON};
+
+const SLOT_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
 pub struct ItemPlugin;
 
 impl Plugin for ItemPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            FixedPreUpdate,
-            equip_item
-                .before(InputSet::BufferClientInputs)
-                .in_set(InputManagerSystem::ManualControl),
-        );
+        app.init_resource::<HeldItemVisuals>()
+            .add_systems(
+                FixedPreUpdate,
+                (release_scroll_while_zooming, release_use_over_ui)
+                    .before(InputSet::BufferClientInputs)
+                    .in_set(InputManagerSystem::ManualControl),
+            )
+            .add_systems(
+                Update,
+                update_held_items
+                    .run_if(in_state(AppState::Game).and(resource_exists::<ItemRegistry>)),
+            );
+    }
+}
+
+pub fn bind_item_actions(input_map: &mut InputMap<CharacterAction>) {
+    for (slot, key) in SLOT_KEYS.into_iter().enumerate() {
+        input_map.insert(CharacterAction::EquipSlot(slot as u8), key);
+    }
+
+    input_map
+        .insert(CharacterAction::Interact, KeyCode::KeyE)
+        .insert(CharacterAction::Interact, GamepadButton::West)
+        .insert(CharacterAction::NextItem, MouseScrollDirection::DOWN)
+        .insert(CharacterAction::PrevItem, MouseScrollDirection::UP)
+        .insert(CharacterAction::NextItem, GamepadButton::RightTrigger)
+        .insert(CharacterAction::PrevItem, GamepadButton::LeftTrigger)
+        .insert(CharacterAction::UsePrimary, MouseButton::Left)
+        .insert(CharacterAction::UseSecondary, MouseButton::Right)
+        .insert(CharacterAction::UsePrimary, GamepadButton::RightTrigger2)
+        .insert(CharacterAction::UseSecondary, GamepadButton::LeftTrigger2);
+}
+
+// The wheel zooms the camera while orbiting, see `camera::orbit`
+fn release_scroll_while_zooming(
+    mut character_action_state: Single<
+        &mut ActionState<CharacterAction>,
+        (With<CharacterMarker>, With<Controlled>),
+    >,
+    mouse: Res<ButtonInput<MouseButton>>,
+) {
+    if mouse.pressed(ORBIT_BUTTON) {
+        character_action_state.release(&CharacterAction::NextItem);
+        character_action_state.release(&CharacterAction::PrevItem);
     }
 }
 
-fn equip_item(
+// Clicks on the hotbar or the inventory window are meant for the UI
+fn release_use_over_ui(
     mut character_action_state: Single<
         &mut ActionState<CharacterAction>,
         (With<CharacterMarker>, With<Controlled>),
     >,
-    mut keyboard_events: EventReader<KeyboardInput>,
+    hover_map: Res<HoverMap>,
 ) {
-    for event in keyboard_events.read() {
-        let slot: f32 = match event.key_code {
-            KeyCode::Digit0 => 0.0,
-            KeyCode::Digit1 => 1.0,
-            KeyCode::Digit2 => 2.0,
-            KeyCode::Digit3 => 3.0,
-            KeyCode::Digit4 => 4.0,
-            KeyCode::Digit5 => 5.0,
-            KeyCode::Digit6 => 6.0,
-            KeyCode::Digit7 => 7.0,
-            KeyCode::Digit8 => 8.0,
-            KeyCode::Digit9 => 9.0,
-            _ => continue,
+    let over_ui = hover_map
+        .get(&PointerId::Mouse)
+        .is_some_and(|hits| !hits.is_empty());
+
+    if over_ui {
+        character_action_state.release(&CharacterAction::UsePrimary);
+        character_action_state.release(&CharacterAction::UseSecondary);
+    }
+}
+
+const HELD_ITEM_OFFSET: Vec3 = Vec3::new(character::CHARACTER_CAPSULE_RADIUS + 0.15, 0.0, -0.2);
+
+/// Model of the item a character holds, a child of the character
+#[derive(Component, Debug)]
+struct HeldItem {
+    id: ItemId,
+    model: Entity,
+}
+
+// Characters swap items all the time, so models are made once per item
+#[derive(Resource, Default)]
+struct HeldItemVisuals(HashMap<ItemId, (Handle<Mesh>, Handle<StandardMaterial>)>);
+
+fn held_item_mesh(kind: &ItemKind) -> Mesh {
+    match kind {
+        ItemKind::Melee { range, .. } => Cuboid::new(0.08, 0.08, range * 0.5).into(),
+        ItemKind::Ranged { .. } => Cuboid::new(0.1, 0.2, 0.3).into(),
+        ItemKind::Block { .. } => Cuboid::from_length(0.3).into(),
+        ItemKind::Consumable { .. } => Sphere::new(0.12).into(),
+    }
+}
+
+// Equipping is predicted and rolled back, and inventory updates can empty the
+// equipped slot, so this follows both components on every character
+fn update_held_items(
+    mut commands: Commands,
+    registry: Res<ItemRegistry>,
+    asset_server: Res<AssetServer>,
+    mut visuals: ResMut<HeldItemVisuals>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    characters: Query<
+        (Entity, &Inventory, &EquippedSlot, Option<&HeldItem>),
+        (
+            With<CharacterMarker>,
+            With<Predicted>,
+            Or<(Changed<Inventory>, Changed<EquippedSlot>)>,
+        ),
+    >,
+) {
+    for (character, inventory, equipped_slot, held_item) in &characters {
+        let item = equipped_slot
+            .stack(inventory)
+            .and_then(|stack| registry.get(stack.id));
+        if held_item.map(|held_item| held_item.id) == item.map(|item| item.id) {
+            continue;
+        }
+
+        if let Some(held_item) = held_item {
+            commands.entity(held_item.model).despawn();
+            commands.entity(character).remove::<HeldItem>();
+        }
+        let Some(item) = item else {
+            continue;
         };
 
-        character_action_state.set_value(&CharacterAction::Equip, slot);
+        let (mesh, material) = visuals
+            .0
+            .entry(item.id)
+            .or_insert_with(|| {
+                (
+                    meshes.add(held_item_mesh(&item.kind)),
+                    materials.add(StandardMaterial {
+                        base_color_texture: Some(asset_server.load(&item.icon)),
+                        ..default()
+                    }),
+                )
+            })
+            .clone();
+
+        let model = commands
+            .spawn((
+                Name::new(format!("Held {}", item.name)),
+                Mesh3d(mesh),
+                MeshMaterial3d(material),
+                Transform::from_translation(HELD_ITEM_OFFSET),
+                ChildOf(character),
+            ))
+            .id();
+        commands
+            .entity(character)
+            .insert(HeldItem { id: item.id, model });
     }
 }
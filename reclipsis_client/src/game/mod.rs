@@ -1,15 +1,29 @@
+use std::collections::HashMap;
+
 use avian3d::prelude::*;
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 use lightyear::prelude::{input::leafwing::SnapshotBuffer, *};
 
-use reclipsis_assets::*;
-use reclipsis_common::{protocol::*, *};
+use reclipsis_assets::{item::ItemRegistry, *};
+use reclipsis_common::{
+    item_use::{ItemBehaviors, ItemUsed, use_equipped_item},
+    protocol::*,
+    *,
+};
 
 use crate::AppState;
 
+mod building;
 mod camera;
+mod chat;
+mod health;
+mod hotbar;
+mod inventory_window;
 mod item;
+mod projectile;
+mod voxel;
+mod world_item;
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, SubStates)]
 #[source(AppState = AppState::Game)]
@@ -24,22 +38,40 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_sub_state::<SpawnedState>()
-            .add_plugins((camera::CameraPlugin, item::ItemPlugin))
+            .init_resource::<BlockVisuals>()
+            .add_plugins((
+                building::BuildingPlugin,
+                camera::CameraPlugin,
+                chat::ChatPlugin,
+                health::HealthPlugin,
+                hotbar::HotbarPlugin,
+                inventory_window::InventoryWindowPlugin,
+                item::ItemPlugin,
+                projectile::ProjectilePlugin,
+                voxel::VoxelPlugin,
+                world_item::WorldItemPlugin,
+            ))
             .add_systems(
                 FixedUpdate,
-                handle_character_actions.run_if(in_state(AppState::Game)),
+                handle_character_actions
+                    .run_if(in_state(AppState::Game).and(resource_exists::<ItemRegistry>)),
+            )
+            .add_systems(
+                Update,
+                (handle_new_character, handle_new_floor).run_if(in_state(AppState::Game)),
             )
             .add_systems(
                 Update,
-                (handle_new_character, handle_new_floor, handle_new_block)
-                    .run_if(in_state(AppState::Game)),
+                handle_new_block
+                    .run_if(in_state(AppState::Game).and(resource_exists::<block::BlockRegistry>)),
             );
     }
 }
 
 fn handle_character_actions(
-    time: Res<Time>,
-    spatial_query: SpatialQuery,
+    environment: character_controller::CharacterEnvironment,
+    item_registry: Res<ItemRegistry>,
+    item_behaviors: Res<ItemBehaviors>,
     mut query: Query<
         (
             &ActionState<CharacterAction>,
@@ -49,18 +81,26 @@ fn handle_character_actions(
         With<Predicted>,
     >,
     timeline: Single<&LocalTimeline>,
+    mut item_used: EventWriter<ItemUsed>,
 ) {
     let tick = timeline.tick();
     for (action_state, input_buffer, mut character) in &mut query {
-        if input_buffer.get(tick).is_some() {
-            apply_character_action(&time, &spatial_query, action_state, &mut character);
-            continue;
-        }
-
-        if let Some((_, prev_action_state)) = input_buffer.get_last_with_tick() {
-            apply_character_action(&time, &spatial_query, prev_action_state, &mut character);
+        let action_state = if input_buffer.get(tick).is_some() {
+            action_state
+        } else if let Some((_, prev_action_state)) = input_buffer.get_last_with_tick() {
+            prev_action_state
         } else {
-            apply_character_action(&time, &spatial_query, action_state, &mut character);
+            action_state
+        };
+
+        apply_character_action(&environment, action_state, &mut character);
+        if let Some(used) = use_equipped_item(
+            &item_registry,
+            &item_behaviors,
+            action_state,
+            &mut character,
+        ) {
+            item_used.write(used);
         }
     }
 }
@@ -80,12 +120,12 @@ fn handle_new_character(
             info!("Adding InputMap to controlled and predicted entity {entity:?}");
 
             // Add InputMap
-            commands.entity(entity).insert(
-                InputMap::new([(CharacterAction::Jump, KeyCode::Space)])
-                    .with(CharacterAction::Jump, GamepadButton::South)
-                    .with_dual_axis(CharacterAction::Move, GamepadStick::LEFT)
-                    .with_dual_axis(CharacterAction::Move, VirtualDPad::wasd()),
-            );
+            let mut input_map = InputMap::new([(CharacterAction::Jump, KeyCode::Space)])
+                .with(CharacterAction::Jump, GamepadButton::South)
+                .with_dual_axis(CharacterAction::Move, GamepadStick::LEFT)
+                .with_dual_axis(CharacterAction::Move, VirtualDPad::wasd());
+            item::bind_item_actions(&mut input_map);
+            commands.entity(entity).insert(input_map);
 
             next_state.set(SpawnedState::Spawned);
         } else {
@@ -126,24 +166,64 @@ fn handle_new_floor(
     }
 }
 
+// Blocks spawn and despawn as they move in and out of interest range, so they share
+// their mesh and one material per block type. Chunks of the voxel world use the
+// materials too
+#[derive(Resource)]
+struct BlockVisuals {
+    mesh: Handle<Mesh>,
+    materials: HashMap<reclipsis_assets::voxel::BlockId, Handle<StandardMaterial>>,
+}
+
+impl FromWorld for BlockVisuals {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(Cuboid::new(
+            block::BLOCK_WIDTH,
+            block::BLOCK_HEIGHT,
+            block::BLOCK_WIDTH,
+        ));
+
+        Self {
+            mesh,
+            materials: HashMap::new(),
+        }
+    }
+}
+
+impl BlockVisuals {
+    fn material(
+        &mut self,
+        block_type: &block::BlockType,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Handle<StandardMaterial> {
+        self.materials
+            .entry(block_type.id)
+            .or_insert_with(|| materials.add(block_type.color()))
+            .clone()
+    }
+}
+
+// Predicted blocks need the same physics as the server's to be simulated alike
 fn handle_new_block(
     mut commands: Commands,
-    block_query: Query<Entity, (Added<Predicted>, With<block::BlockMarker>)>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    block_query: Query<(Entity, &block::BlockKind), (Added<Predicted>, With<block::BlockMarker>)>,
+    registry: Res<block::BlockRegistry>,
+    mut block_visuals: ResMut<BlockVisuals>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    for entity in &block_query {
-        info!("Handling new block");
+    for (entity, kind) in &block_query {
+        let Some(block_type) = registry.get(kind.0) else {
+            warn!("New block has unknown kind {:?}", kind.0);
+            continue;
+        };
+
+        info!("Handling new {} block", block_type.name);
         commands
             .entity(entity)
-            .insert(block::BlockPhysicsBundle::default())
+            .insert(block::BlockPhysicsBundle::new(block_type))
             .insert((
-                Mesh3d(meshes.add(Cuboid::new(
-                    block::BLOCK_WIDTH,
-                    block::BLOCK_HEIGHT,
-                    block::BLOCK_WIDTH,
-                ))),
-                MeshMaterial3d(materials.add(Color::srgb(1.0, 0.0, 1.0))),
+                Mesh3d(block_visuals.mesh.clone()),
+                MeshMaterial3d(block_visuals.material(block_type, &mut materials)),
             ));
     }
 }
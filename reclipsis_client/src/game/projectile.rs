@@ -0,0 +1,294 @@
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use reclipsis_assets::{
+    character::CharacterMarker,
+    item::{ItemKind, ItemRegistry},
+    projectile::{PROJECTILE_RADIUS, Projectile, ProjectilePhysicsBundle},
+};
+use reclipsis_common::{
+    item_use::{ItemUsed, UseAction},
+    projectile::prespawn_projectile,
+};
+
+use crate::AppState;
+
+// Predicted projectiles the server didn't confirm by then were mispredicted
+const PREDICTED_SPAWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProjectileVisuals>()
+            .add_systems(
+                FixedUpdate,
+                spawn_predicted_projectiles
+                    .after(super::handle_character_actions)
+                    .run_if(in_state(AppState::Game).and(resource_exists::<ItemRegistry>)),
+            )
+            .add_systems(
+                Update,
+                (handle_new_projectile, despawn_mispredicted_projectiles)
+                    .run_if(in_state(AppState::Game)),
+            );
+    }
+}
+
+/// Projectile the controlled character shot, until the server's one replaces it
+#[derive(Component, Debug)]
+struct PredictedLaunch {
+    spawned_at: Duration,
+}
+
+#[derive(Resource)]
+struct ProjectileVisuals {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for ProjectileVisuals {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Sphere::new(PROJECTILE_RADIUS));
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(Color::srgb(0.4, 0.4, 0.4));
+
+        Self { mesh, material }
+    }
+}
+
+// Launched from the same state and tick as on the server, so lightyear matches
+// it to the server's projectile once that replicates
+fn spawn_predicted_projectiles(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    registry: Res<ItemRegistry>,
+    mut item_used: EventReader<ItemUsed>,
+    // Rollbacks replay ticks whose shots were already spawned
+    mut last_spawned_tick: Local<Option<Tick>>,
+    characters: Query<
+        (&Position, &Rotation, Option<&CollisionLayers>),
+        (With<CharacterMarker>, With<Controlled>),
+    >,
+    client: Single<(&LocalId, &LocalTimeline), With<Client>>,
+) {
+    let (local_id, timeline) = client.into_inner();
+    let tick = timeline.tick();
+    if last_spawned_tick.is_some_and(|last| tick - last <= 0) {
+        item_used.clear();
+        return;
+    }
+    *last_spawned_tick = Some(tick);
+
+    for used in item_used.read() {
+        if used.action != UseAction::Primary {
+            continue;
+        }
+        // Other players' projectiles only come from the server
+        let Ok((position, rotation, collision_layers)) = characters.get(used.character) else {
+            continue;
+        };
+        let Some(projectile) = registry.get(used.item).and_then(|item| {
+            prespawn_projectile(
+                item,
+                position.0,
+                rotation.0,
+                collision_layers.copied().unwrap_or_default(),
+                local_id.0,
+            )
+        }) else {
+            continue;
+        };
+
+        commands.spawn((
+            projectile,
+            PredictedLaunch {
+                spawned_at: time.elapsed(),
+            },
+        ));
+    }
+}
+
+fn handle_new_projectile(
+    mut commands: Commands,
+    projectiles: Query<
+        (Entity, &Projectile, Has<RigidBody>),
+        (Added<Projectile>, Without<Confirmed>),
+    >,
+    registry: Option<Res<ItemRegistry>>,
+    visuals: Res<ProjectileVisuals>,
+) {
+    for (entity, projectile, has_body) in &projectiles {
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert((
+            Mesh3d(visuals.mesh.clone()),
+            MeshMaterial3d(visuals.material.clone()),
+        ));
+
+        // Our own projectiles were launched with their physics already
+        if has_body {
+            continue;
+        }
+        let gravity_scale = registry
+            .as_ref()
+            .and_then(|registry| registry.get(projectile.item))
+            .and_then(|item| match item.kind {
+                ItemKind::Ranged { gravity_scale, .. } => Some(gravity_scale),
+                _ => None,
+            });
+        if let Some(gravity_scale) = gravity_scale {
+            entity_commands.insert(ProjectilePhysicsBundle::new(gravity_scale));
+        }
+    }
+}
+
+// Lightyear stops treating a projectile as pre-spawned once it matched the
+// server's, the rest were never shot on the server
+fn despawn_mispredicted_projectiles(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    projectiles: Query<(Entity, &PredictedLaunch), With<PreSpawned>>,
+) {
+    for (entity, launch) in &projectiles {
+        if time.elapsed() - launch.spawned_at >= PREDICTED_SPAWN_TIMEOUT {
+            debug!("Despawning mispredicted projectile {entity:?}");
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use reclipsis_assets::{inventory::ItemId, item::Item};
+
+    use super::*;
+
+    const SLINGSHOT: ItemId = ItemId(2);
+
+    fn slingshot() -> Item {
+        Item {
+            id: SLINGSHOT,
+            name: "Slingshot".to_string(),
+            description: String::new(),
+            icon: String::new(),
+            max_stack: 1,
+            kind: ItemKind::Ranged {
+                damage: 15.0,
+                projectile_speed: 30.0,
+                gravity_scale: 0.5,
+                lifetime_secs: 3.0,
+                cooldown_secs: 0.8,
+            },
+        }
+    }
+
+    fn shoot(app: &mut App, character: Entity) {
+        app.world_mut().send_event(ItemUsed {
+            character,
+            item: SLINGSHOT,
+            slot: 0,
+            action: UseAction::Primary,
+        });
+        app.update();
+    }
+
+    // The server's `spawn_projectiles` test expects the same values for this shot
+    #[test]
+    fn shots_spawn_the_projectile_the_server_replicates() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<ItemUsed>()
+            .insert_resource(ItemRegistry::new([slingshot()]).unwrap())
+            .add_systems(Update, spawn_predicted_projectiles);
+
+        let peer = PeerId::Netcode(7);
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        // Facing -X
+        let rotation = Quat::from_rotation_y(FRAC_PI_2);
+        let layers = CollisionLayers::new(LayerMask(1 << 2), LayerMask(1 << 2));
+
+        app.world_mut()
+            .spawn((Client::default(), LocalId(peer), LocalTimeline::default()));
+        let character = app
+            .world_mut()
+            .spawn((
+                CharacterMarker,
+                Controlled,
+                Position(position),
+                Rotation(rotation),
+                layers,
+            ))
+            .id();
+        // Other players' shots only come from the server
+        let other = app
+            .world_mut()
+            .spawn((CharacterMarker, Position(position), Rotation(rotation)))
+            .id();
+        app.world_mut().send_event(ItemUsed {
+            character: other,
+            item: SLINGSHOT,
+            slot: 0,
+            action: UseAction::Primary,
+        });
+        shoot(&mut app, character);
+
+        let mut projectiles = app
+            .world_mut()
+            .query_filtered::<Entity, (With<Projectile>, With<PredictedLaunch>)>();
+        let projectile = projectiles.single(app.world()).unwrap();
+
+        // Just outside the capsule, at 30 m/s aimed 0.1 upwards
+        let world = app.world();
+        let launched_at = world.get::<Position>(projectile).unwrap().0;
+        assert!(launched_at.abs_diff_eq(Vec3::new(0.3, 2.0, 3.0), 1e-5));
+        let velocity = world.get::<LinearVelocity>(projectile).unwrap().0;
+        let expected = Vec3::new(-1.0, 0.1, 0.0) * 30.0 / 1.01f32.sqrt();
+        assert!(velocity.abs_diff_eq(expected, 1e-4));
+        assert_eq!(world.get::<CollisionLayers>(projectile), Some(&layers));
+
+        // Lightyear computes the hash from the spawn tick and this salt after the spawn
+        let prespawned = world.get::<PreSpawned>(projectile).unwrap();
+        assert_eq!(prespawned.user_salt, Some(peer.to_bits()));
+        assert_eq!(prespawned.hash, None);
+    }
+
+    #[test]
+    fn rollbacks_dont_shoot_again() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<ItemUsed>()
+            .insert_resource(ItemRegistry::new([slingshot()]).unwrap())
+            .add_systems(Update, spawn_predicted_projectiles);
+
+        app.world_mut().spawn((
+            Client::default(),
+            LocalId(PeerId::Netcode(7)),
+            LocalTimeline::default(),
+        ));
+        let character = app
+            .world_mut()
+            .spawn((
+                CharacterMarker,
+                Controlled,
+                Position(Vec3::ZERO),
+                Rotation::default(),
+            ))
+            .id();
+
+        // The timeline doesn't advance, like a replay of the tick of the first shot
+        shoot(&mut app, character);
+        shoot(&mut app, character);
+
+        let mut projectiles = app
+            .world_mut()
+            .query_filtered::<(), (With<Projectile>, With<PredictedLaunch>)>();
+        assert_eq!(projectiles.iter(app.world()).count(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use reclipsis_assets::{
+    block::BlockRegistry,
+    voxel::{self, Chunk, VoxelChunk, VoxelWorld},
+};
+use reclipsis_common::protocol::WorldUpdate;
+
+use crate::{AppState, game::BlockVisuals};
+
+pub struct VoxelPlugin;
+
+impl Plugin for VoxelPlugin {
+    fn build(&self, app: &mut App) {
+        // Chunks can arrive before the character, so updates are received outside the game state
+        app.add_systems(Update, receive_world_updates)
+            .add_systems(
+                PostUpdate,
+                update_chunk_meshes
+                    .after(reclipsis_common::voxel::sync_chunk_colliders)
+                    .run_if(in_state(AppState::Game).and(resource_exists::<BlockRegistry>)),
+            )
+            .add_observer(add_voxel_world)
+            .add_observer(scope_chunk);
+    }
+}
+
+/// Whether the instance of the client's world can be built in, as the server
+/// decides when sending it
+#[derive(Component, Default, Debug)]
+pub struct BuildingAllowed(pub bool);
+
+// The client only ever sees the world of its character's instance
+fn add_voxel_world(trigger: Trigger<OnAdd, Client>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert((VoxelWorld::default(), BuildingAllowed::default()));
+}
+
+fn scope_chunk(trigger: Trigger<OnAdd, VoxelChunk>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert(StateScoped(AppState::Game));
+}
+
+// Predicted building edits the same world, the server's edits overwrite it
+fn receive_world_updates(
+    client: Single<
+        (
+            &mut MessageReceiver<WorldUpdate>,
+            &mut VoxelWorld,
+            &mut BuildingAllowed,
+        ),
+        With<Client>,
+    >,
+) {
+    let (mut receiver, mut world, mut building_allowed) = client.into_inner();
+
+    for update in receiver.receive() {
+        match update {
+            WorldUpdate::Reset { allows_building } => {
+                world.clear();
+                building_allowed.0 = allows_building;
+            }
+            WorldUpdate::Chunk { pos, chunk } => match Chunk::decompress(&chunk) {
+                Some(chunk) => world.insert_chunk(pos, chunk),
+                None => warn!("Received an invalid chunk at {pos}"),
+            },
+            WorldUpdate::Edits { chunk, edits } => {
+                for (index, block) in edits {
+                    match voxel::chunk_cell(chunk, index) {
+                        Some(cell) => {
+                            world.set(cell, block);
+                        }
+                        None => warn!("Received an invalid edit in chunk {chunk} at {index}"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Colliders are replaced whenever a chunk changes, the mesh follows them
+fn update_chunk_meshes(
+    mut commands: Commands,
+    chunks: Query<(Entity, &VoxelChunk), Changed<Collider>>,
+    worlds: Query<&VoxelWorld>,
+    registry: Res<BlockRegistry>,
+    mut block_visuals: ResMut<BlockVisuals>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, voxel_chunk) in &chunks {
+        let Some(chunk) = worlds
+            .get(voxel_chunk.world)
+            .ok()
+            .and_then(|world| world.chunk(voxel_chunk.pos))
+        else {
+            continue;
+        };
+        let Some(block_type) = registry.get(voxel_chunk.block) else {
+            continue;
+        };
+
+        let mut box_meshes = chunk
+            .merged_boxes()
+            .into_iter()
+            .filter(|merged| merged.block == voxel_chunk.block)
+            .map(|merged| {
+                Mesh::from(Cuboid::from_size(merged.extents())).translated_by(merged.center())
+            });
+        let Some(mut mesh) = box_meshes.next() else {
+            continue;
+        };
+        for box_mesh in box_meshes {
+            if let Err(err) = mesh.merge(&box_mesh) {
+                warn!("Can't merge the mesh of chunk {}: {err}", voxel_chunk.pos);
+            }
+        }
+
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(mesh)),
+            MeshMaterial3d(block_visuals.material(block_type, &mut materials)),
+        ));
+    }
+}
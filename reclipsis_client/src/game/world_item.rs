@@ -0,0 +1,206 @@
+use std::{collections::HashMap, time::Duration};
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use lightyear::prelude::*;
+use reclipsis_assets::{
+    character::CharacterMarker,
+    health::Dead,
+    inventory::{Inventory, ItemId},
+    item::{Item, ItemRegistry},
+    world_item::{PickupDelay, WORLD_ITEM_SIZE, WorldItem, WorldItemPhysicsBundle},
+};
+use reclipsis_common::{pickup, protocol::CharacterAction};
+
+use crate::AppState;
+
+// Long enough for the server's answer to a pickup to arrive
+const PICKUP_CONFIRM_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct WorldItemPlugin;
+
+impl Plugin for WorldItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldItemVisuals>()
+            .add_systems(
+                FixedUpdate,
+                predict_pickups
+                    .after(super::handle_character_actions)
+                    .run_if(in_state(AppState::Game).and(resource_exists::<ItemRegistry>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    handle_new_world_item.run_if(resource_exists::<ItemRegistry>),
+                    resolve_predicted_pickups,
+                )
+                    .run_if(in_state(AppState::Game)),
+            );
+    }
+}
+
+/// World item the controlled character picked up before the server confirmed it,
+/// hidden until the server despawns it
+#[derive(Component, Debug)]
+struct PredictedPickup {
+    deadline: Duration,
+}
+
+/// Predicted pickups waiting on the server, the inventory is reset to the
+/// server's once they time out
+#[derive(Component, Debug, Default)]
+struct PendingPickups {
+    deadline: Option<Duration>,
+}
+
+/// World items stream in and out of interest range, so they share their mesh
+/// and one material per item
+#[derive(Resource)]
+struct WorldItemVisuals {
+    mesh: Handle<Mesh>,
+    materials: HashMap<ItemId, Handle<StandardMaterial>>,
+}
+
+impl FromWorld for WorldItemVisuals {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cuboid::from_length(WORLD_ITEM_SIZE));
+
+        Self {
+            mesh,
+            materials: HashMap::new(),
+        }
+    }
+}
+
+impl WorldItemVisuals {
+    fn material(
+        &mut self,
+        item: &Item,
+        asset_server: &AssetServer,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Handle<StandardMaterial> {
+        self.materials
+            .entry(item.id)
+            .or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color_texture: Some(asset_server.load(&item.icon)),
+                    ..default()
+                })
+            })
+            .clone()
+    }
+}
+
+fn handle_new_world_item(
+    mut commands: Commands,
+    items: Query<(Entity, &WorldItem), Added<Predicted>>,
+    registry: Res<ItemRegistry>,
+    asset_server: Res<AssetServer>,
+    mut visuals: ResMut<WorldItemVisuals>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, world_item) in &items {
+        let Some(item) = registry.get(world_item.stack.id) else {
+            warn!("New world item has unknown item {:?}", world_item.stack.id);
+            continue;
+        };
+
+        commands
+            .entity(entity)
+            .insert(WorldItemPhysicsBundle::default())
+            .insert((
+                Mesh3d(visuals.mesh.clone()),
+                MeshMaterial3d(visuals.material(item, &asset_server, &mut materials)),
+            ));
+    }
+}
+
+fn predict_pickups(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    registry: Res<ItemRegistry>,
+    character: Single<
+        (
+            Entity,
+            &ActionState<CharacterAction>,
+            &Position,
+            Has<Dead>,
+            &mut Inventory,
+        ),
+        (With<CharacterMarker>, With<Controlled>, With<Predicted>),
+    >,
+    items: Query<
+        (Entity, &WorldItem, &Position, Option<&PickupDelay>),
+        (With<Predicted>, Without<PredictedPickup>),
+    >,
+) {
+    let (entity, action_state, position, dead, mut inventory) = character.into_inner();
+
+    let nearby = items
+        .iter()
+        .map(|(item, world_item, item_position, delay)| {
+            (item, item_position.0, world_item.stack, delay)
+        });
+    let deadline = time.elapsed() + PICKUP_CONFIRM_TIMEOUT;
+
+    let picked_up = pickup::pick_up_items(
+        &registry,
+        action_state,
+        position.0,
+        dead,
+        &mut inventory,
+        nearby,
+    );
+    for item in picked_up {
+        commands.entity(item).insert((
+            PredictedPickup { deadline },
+            Visibility::Hidden,
+            ColliderDisabled,
+        ));
+        commands.entity(entity).insert(PendingPickups {
+            deadline: Some(deadline),
+        });
+    }
+}
+
+// Picked up items are despawned by the server. Ones still around after the
+// timeout were rejected, e.g. because someone else got there first
+fn resolve_predicted_pickups(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    items: Query<(Entity, &PredictedPickup)>,
+    mut characters: Query<(&Predicted, &mut Inventory, &mut PendingPickups)>,
+    confirmed: Query<&Inventory, (With<Confirmed>, Without<PendingPickups>)>,
+) {
+    let now = time.elapsed();
+
+    for (item, pickup) in &items {
+        if now >= pickup.deadline {
+            commands
+                .entity(item)
+                .remove::<(PredictedPickup, ColliderDisabled)>()
+                .insert(Visibility::Inherited);
+        }
+    }
+
+    for (predicted, mut inventory, mut pending) in &mut characters {
+        if pending.deadline.is_none_or(|deadline| now < deadline) {
+            continue;
+        }
+        pending.deadline = None;
+
+        // By now the server's inventory includes every pickup it accepted
+        let Some(confirmed_inventory) = predicted
+            .confirmed_entity
+            .and_then(|confirmed_entity| confirmed.get(confirmed_entity).ok())
+        else {
+            continue;
+        };
+        if *inventory != *confirmed_inventory {
+            *inventory = confirmed_inventory.clone();
+        }
+    }
+}
@@ -9,6 +9,9 @@ use bevy_inspector_egui::{bevy_egui::EguiPlugin, quick::WorldInspectorPlugin};
 mod game;
 mod menu;
 
+// The assets directory is shared with the server at the workspace root
+const ASSETS_PATH: &str = "../assets";
+
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum AppState {
     #[default]
@@ -18,7 +21,10 @@ pub enum AppState {
 
 fn main() {
     App::new()
-        .add_plugins(DefaultPlugins)
+        .add_plugins(DefaultPlugins.set(AssetPlugin {
+            file_path: ASSETS_PATH.to_string(),
+            ..default()
+        }))
         .add_plugins(reclipsis_common::SharedPlugin)
         .add_plugins(client::ClientPlugins {
             tick_duration: Duration::from_secs_f64(1.0 / FIXED_TIMESTEP_HZ),
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use reclipsis_assets::{
+    block::BlockRegistry, character::CharacterMarker, definitions::Registry, item::ItemRegistry,
+};
+use reclipsis_common::protocol::{QueueStatus, RegistryChecksum};
+
+use crate::AppState;
+
+pub struct ConnectionPlugin;
+
+impl Plugin for ConnectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (receive_queue_status, enter_game).run_if(in_state(AppState::Menu)),
+        )
+        .add_systems(
+            Update,
+            check_registry_checksum
+                .run_if(resource_exists::<ItemRegistry>.and(resource_exists::<BlockRegistry>)),
+        )
+        .add_observer(handle_disconnected);
+    }
+}
+
+/// Shown instead of a plain "Disconnected" when we ended the connection ourselves
+#[derive(Component)]
+struct DisconnectReason(String);
+
+/// Shows what the connection to the selected server is doing
+#[derive(Component)]
+pub struct ConnectionStatusText;
+
+fn receive_queue_status(
+    mut receiver: Single<&mut MessageReceiver<QueueStatus>, With<Client>>,
+    mut status_text: Query<&mut Text, With<ConnectionStatusText>>,
+) {
+    for status in receiver.receive() {
+        let text = match status.position {
+            0 => "Joining...".to_string(),
+            position => format!(
+                "Server is full, queue position {position} of {}",
+                status.length
+            ),
+        };
+
+        for mut status_text in &mut status_text {
+            status_text.0.clone_from(&text);
+        }
+    }
+}
+
+// Item and block ids mean nothing unless both sides loaded the same definitions
+fn check_registry_checksum(
+    mut commands: Commands,
+    items: Res<ItemRegistry>,
+    blocks: Res<BlockRegistry>,
+    mut receiver: Single<(Entity, &mut MessageReceiver<RegistryChecksum>), With<Client>>,
+) {
+    let (client, receiver) = &mut *receiver;
+
+    for checksum in receiver.receive() {
+        let reason = if checksum.items != items.checksum() {
+            error!(
+                "Item registry checksum {:016x} doesn't match the server's {:016x}",
+                items.checksum(),
+                checksum.items
+            );
+            "Item definitions don't match the server"
+        } else if checksum.blocks != blocks.checksum() {
+            error!(
+                "Block registry checksum {:016x} doesn't match the server's {:016x}",
+                blocks.checksum(),
+                checksum.blocks
+            );
+            "Block definitions don't match the server"
+        } else {
+            continue;
+        };
+
+        commands
+            .entity(*client)
+            .insert(DisconnectReason(reason.to_string()));
+        commands.trigger_targets(Disconnect, *client);
+    }
+}
+
+// The server only spawns our character once we got a player slot
+fn enter_game(
+    character: Query<(), (With<CharacterMarker>, With<Controlled>)>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !character.is_empty() {
+        next_state.set(AppState::Game);
+    }
+}
+
+fn handle_disconnected(
+    trigger: Trigger<OnRemove, Connected>,
+    mut commands: Commands,
+    clients: Query<Option<&DisconnectReason>, With<Client>>,
+    mut status_text: Query<&mut Text, With<ConnectionStatusText>>,
+) {
+    let Ok(reason) = clients.get(trigger.target()) else {
+        return;
+    };
+
+    info!("Disconnected from server");
+
+    let text = reason.map_or("Disconnected", |reason| &reason.0);
+    for mut status_text in &mut status_text {
+        status_text.0 = text.to_string();
+    }
+
+    commands.entity(trigger.target()).despawn();
+}
@@ -1,3 +1,5 @@
+use std::net::SocketAddr;
+
 use bevy::prelude::*;
 use lightyear::{
     netcode::{Key, NetcodeClient},
@@ -6,17 +8,33 @@ use lightyear::{
 
 use crate::AppState;
 
+mod connection;
+mod server_browser;
+
 pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::Menu), debug_connect);
+        app.enable_state_scoped_entities::<AppState>().add_plugins((
+            connection::ConnectionPlugin,
+            server_browser::ServerBrowserPlugin,
+        ));
     }
 }
 
-fn debug_connect(mut commands: Commands, mut next_state: ResMut<NextState<AppState>>) {
+fn connect(
+    commands: &mut Commands,
+    clients: &Query<(), With<Client>>,
+    status_text: &mut Text,
+    server_addr: SocketAddr,
+) {
+    if !clients.is_empty() {
+        return;
+    }
+
+    info!("Connecting to {server_addr}");
+
     let client_addr = "127.0.0.1:1337".parse().unwrap();
-    let server_addr = "127.0.0.1:8080".parse().unwrap();
 
     let auth = Authentication::Manual {
         server_addr,
@@ -39,5 +57,5 @@ fn debug_connect(mut commands: Commands, mut next_state: ResMut<NextState<AppSta
         .id();
     commands.trigger_targets(Connect, client);
 
-    next_state.set(AppState::Game);
+    status_text.0 = format!("Connecting to {server_addr}...");
 }
@@ -0,0 +1,277 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use lightyear::prelude::Client;
+use reclipsis_master::*;
+
+use crate::{
+    AppState,
+    menu::{connect, connection::ConnectionStatusText},
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+const LOCAL_SERVER_ADDR: &str = "127.0.0.1:8080";
+
+pub struct ServerBrowserPlugin;
+
+impl Plugin for ServerBrowserPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Menu), (setup_browser, spawn_menu))
+            .add_systems(OnExit(AppState::Menu), cleanup_browser)
+            .add_systems(
+                Update,
+                (refresh_server_list, receive_packets, update_server_list)
+                    .chain()
+                    .run_if(in_state(AppState::Menu).and(resource_exists::<ServerBrowser>)),
+            );
+    }
+}
+
+#[derive(Debug)]
+struct ServerEntry {
+    listing: ServerListing,
+    ping: Option<Duration>,
+}
+
+#[derive(Resource)]
+struct ServerBrowser {
+    socket: UdpSocket,
+    master_addr: SocketAddr,
+    servers: Vec<ServerEntry>,
+    // Nonce -> (query address, time sent)
+    pending_pings: HashMap<u64, (SocketAddr, Instant)>,
+    next_nonce: u64,
+    refresh_timer: Timer,
+}
+
+impl ServerBrowser {
+    fn request_list(&self) {
+        if let Err(err) = self
+            .socket
+            .send_to(&MasterPacket::ListRequest.to_bytes(), self.master_addr)
+        {
+            warn!("Failed to request server list: {err}");
+        }
+    }
+
+    fn ping(&mut self, query_addr: SocketAddr) {
+        let nonce = self.next_nonce;
+        self.next_nonce += 1;
+
+        if self
+            .socket
+            .send_to(&MasterPacket::Ping(nonce).to_bytes(), query_addr)
+            .is_ok()
+        {
+            self.pending_pings
+                .insert(nonce, (query_addr, Instant::now()));
+        }
+    }
+}
+
+#[derive(Component)]
+struct ServerList;
+
+fn setup_browser(mut commands: Commands) {
+    let socket = match UdpSocket::bind("0.0.0.0:0").and_then(|socket| {
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }) {
+        Ok(socket) => socket,
+        Err(err) => {
+            error!("Failed to open server browser socket: {err}");
+            return;
+        }
+    };
+
+    let mut refresh_timer = Timer::new(REFRESH_INTERVAL, TimerMode::Repeating);
+    // Query the master right away
+    refresh_timer.tick(REFRESH_INTERVAL);
+
+    commands.insert_resource(ServerBrowser {
+        socket,
+        master_addr: DEFAULT_MASTER_ADDR.parse().unwrap(),
+        servers: Vec::new(),
+        pending_pings: HashMap::new(),
+        next_nonce: 0,
+        refresh_timer,
+    });
+}
+
+fn cleanup_browser(mut commands: Commands) {
+    commands.remove_resource::<ServerBrowser>();
+}
+
+fn spawn_menu(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("Server Browser"),
+            StateScoped(AppState::Menu),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(32.0)),
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Servers"),
+                TextFont {
+                    font_size: 36.0,
+                    ..default()
+                },
+            ));
+
+            parent.spawn((
+                ServerList,
+                Node {
+                    width: Val::Px(640.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+            ));
+
+            spawn_button(parent, "Refresh").observe(
+                |_: Trigger<Pointer<Click>>, browser: Res<ServerBrowser>| {
+                    browser.request_list();
+                },
+            );
+
+            spawn_button(parent, "Connect to localhost").observe(
+                |_: Trigger<Pointer<Click>>,
+                 mut commands: Commands,
+                 clients: Query<(), With<Client>>,
+                 mut status_text: Single<&mut Text, With<ConnectionStatusText>>| {
+                    connect(
+                        &mut commands,
+                        &clients,
+                        &mut status_text,
+                        LOCAL_SERVER_ADDR.parse().unwrap(),
+                    );
+                },
+            );
+
+            parent.spawn((ConnectionStatusText, Text::default()));
+        });
+}
+
+fn spawn_button<'a>(parent: &'a mut ChildSpawnerCommands, label: &str) -> EntityCommands<'a> {
+    let mut button = parent.spawn((
+        Button,
+        Node {
+            padding: UiRect::axes(Val::Px(16.0), Val::Px(8.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+    ));
+    button.with_child(Text::new(label));
+    button
+}
+
+fn refresh_server_list(time: Res<Time>, mut browser: ResMut<ServerBrowser>) {
+    // Ticking the timer shouldn't rebuild the server list
+    let browser = browser.bypass_change_detection();
+    if browser.refresh_timer.tick(time.delta()).just_finished() {
+        browser.request_list();
+    }
+}
+
+fn receive_packets(mut browser: ResMut<ServerBrowser>) {
+    let mut buf = [0; MAX_PACKET_SIZE];
+
+    loop {
+        let (len, src) = match browser.socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+            Err(err) => {
+                warn!("Failed to receive on server browser socket: {err}");
+                return;
+            }
+        };
+
+        match MasterPacket::from_bytes(&buf[..len]) {
+            Some(MasterPacket::ListResponse(listings)) if src == browser.master_addr => {
+                let previous = std::mem::take(&mut browser.servers);
+                browser.pending_pings.clear();
+                for listing in listings {
+                    // Keep the last ping until the new one comes back
+                    let ping = previous
+                        .iter()
+                        .find(|entry| entry.listing.query_addr == listing.query_addr)
+                        .and_then(|entry| entry.ping);
+
+                    browser.ping(listing.query_addr);
+                    browser.servers.push(ServerEntry { listing, ping });
+                }
+            }
+            Some(MasterPacket::Pong(nonce)) => {
+                let Some((query_addr, sent)) = browser.pending_pings.remove(&nonce) else {
+                    continue;
+                };
+                if query_addr != src {
+                    continue;
+                }
+
+                if let Some(entry) = browser
+                    .servers
+                    .iter_mut()
+                    .find(|entry| entry.listing.query_addr == query_addr)
+                {
+                    entry.ping = Some(sent.elapsed());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_server_list(
+    mut commands: Commands,
+    browser: Res<ServerBrowser>,
+    server_list: Single<Entity, With<ServerList>>,
+) {
+    if !browser.is_changed() {
+        return;
+    }
+
+    commands
+        .entity(*server_list)
+        .despawn_related::<Children>()
+        .with_children(|parent| {
+            if browser.servers.is_empty() {
+                parent.spawn(Text::new("No servers found"));
+            }
+
+            for entry in &browser.servers {
+                let info = &entry.listing.info;
+                let ping = match entry.ping {
+                    Some(ping) => format!("{} ms", ping.as_millis()),
+                    None => "?".to_string(),
+                };
+                let label = format!(
+                    "{}  |  {}  |  {}/{}  |  {ping}",
+                    info.name, info.map, info.players, info.max_players
+                );
+
+                let address = info.address;
+                spawn_button(parent, &label).observe(
+                    move |_: Trigger<Pointer<Click>>,
+                          mut commands: Commands,
+                          clients: Query<(), With<Client>>,
+                          mut status_text: Single<&mut Text, With<ConnectionStatusText>>| {
+                        connect(&mut commands, &clients, &mut status_text, address);
+                    },
+                );
+            }
+        });
+}
@@ -0,0 +1,129 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use reclipsis_assets::{
+    block::{self, BLOCK_HEIGHT, BLOCK_WIDTH},
+    inventory::InventoryError,
+    voxel::VoxelWorld,
+};
+
+/// Characters place and remove blocks within this distance of the cell's center
+pub const BUILD_REACH: f32 = 6.0;
+/// Clients check the reach against their predicted position, which is a bit ahead,
+/// so the server allows this much more
+pub const REACH_TOLERANCE: f32 = 1.0;
+// Blocks and characters only touching the cell don't occupy it
+const OVERLAP_MARGIN: f32 = 0.05;
+
+/// Why a build request is refused, by the server or already by the client's
+/// prediction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildRejection {
+    NoCharacter,
+    Dead,
+    NotAllowed,
+    OutOfReach,
+    NotPlaceable,
+    Occupied,
+    NoBlock,
+    Inventory(InventoryError),
+}
+
+pub fn in_reach(character: Vec3, cell: IVec3, tolerance: f32) -> bool {
+    character.distance(block::cell_center(cell)) <= BUILD_REACH + tolerance
+}
+
+/// Checks shared by placing and removing
+pub fn check_access(
+    dead: bool,
+    allows_building: bool,
+    character: Vec3,
+    cell: IVec3,
+    reach_tolerance: f32,
+) -> Result<(), BuildRejection> {
+    if dead {
+        return Err(BuildRejection::Dead);
+    }
+    if !allows_building {
+        return Err(BuildRejection::NotAllowed);
+    }
+    if !in_reach(character, cell, reach_tolerance) {
+        return Err(BuildRejection::OutOfReach);
+    }
+
+    Ok(())
+}
+
+/// Whether a block placed in `cell` would overlap a block of `world` or a body
+/// for which `blocks_placement` holds, like characters and free-moving blocks
+pub fn is_occupied(
+    world: &VoxelWorld,
+    spatial_query: &SpatialQuery,
+    filter: &SpatialQueryFilter,
+    cell: IVec3,
+    blocks_placement: impl Fn(Entity) -> bool,
+) -> bool {
+    if !world.get(cell).is_air() {
+        return true;
+    }
+
+    // Chunk colliders of neighbouring cells only touch the shrunk cell
+    spatial_query
+        .shape_intersections(
+            &Collider::cuboid(
+                BLOCK_WIDTH - OVERLAP_MARGIN,
+                BLOCK_HEIGHT - OVERLAP_MARGIN,
+                BLOCK_WIDTH - OVERLAP_MARGIN,
+            ),
+            block::cell_center(cell),
+            Quat::IDENTITY,
+            filter,
+        )
+        .into_iter()
+        .any(blocks_placement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: IVec3 = IVec3::new(0, 0, 0);
+
+    fn at_distance(distance: f32) -> Vec3 {
+        block::cell_center(CELL) + Vec3::X * distance
+    }
+
+    #[test]
+    fn reach_includes_the_tolerance() {
+        assert!(in_reach(at_distance(BUILD_REACH - 0.01), CELL, 0.0));
+        assert!(!in_reach(at_distance(BUILD_REACH + 0.5), CELL, 0.0));
+        assert!(in_reach(
+            at_distance(BUILD_REACH + 0.5),
+            CELL,
+            REACH_TOLERANCE
+        ));
+        assert!(!in_reach(
+            at_distance(BUILD_REACH + REACH_TOLERANCE + 0.5),
+            CELL,
+            REACH_TOLERANCE
+        ));
+    }
+
+    #[test]
+    fn access_requires_a_living_character_in_reach_where_building_is_allowed() {
+        let near = at_distance(1.0);
+
+        assert_eq!(check_access(false, true, near, CELL, 0.0), Ok(()));
+        assert_eq!(
+            check_access(true, true, near, CELL, 0.0),
+            Err(BuildRejection::Dead)
+        );
+        assert_eq!(
+            check_access(false, false, near, CELL, 0.0),
+            Err(BuildRejection::NotAllowed)
+        );
+        assert_eq!(
+            check_access(false, true, at_distance(BUILD_REACH + 0.5), CELL, 0.0),
+            Err(BuildRejection::OutOfReach)
+        );
+    }
+}
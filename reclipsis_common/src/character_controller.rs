@@ -0,0 +1,634 @@
+use avian3d::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use reclipsis_assets::{block, character};
+
+use crate::{CharacterQueryItem, JUMP_SPEED, MAX_ACCELERATION, MAX_SPEED};
+
+/// Steepest slope characters can stand on and walk up
+pub const MAX_SLOPE_ANGLE: f32 = 50.0_f32.to_radians();
+/// Ledges up to this high are walked onto without jumping, a block takes a jump
+pub const STEP_HEIGHT: f32 = 0.35;
+// Characters walking down slopes and steps stay on the ground within this distance
+const SNAP_DISTANCE: f32 = 0.4;
+// Gap kept between characters and what they touch, so casts never start inside it
+const SKIN_WIDTH: f32 = 0.02;
+// Grounded characters hover at the skin width above the ground
+const GROUND_PROBE_DISTANCE: f32 = 2.0 * SKIN_WIDTH;
+// Corners need two slides, anything more is a crevice the character stops in
+const MAX_SLIDES: usize = 4;
+
+/// Ticks after walking off a ledge in which characters can still jump
+pub const COYOTE_TICKS: u8 = 6;
+/// Ticks a jump press is kept for when it comes just before landing
+pub const JUMP_BUFFER_TICKS: u8 = 6;
+// Releasing the jump button while rising cuts the upward velocity by this
+const JUMP_RELEASE_FACTOR: f32 = 0.5;
+
+/// The jump button on the current tick
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JumpInput {
+    pub just_pressed: bool,
+    pub pressed: bool,
+}
+
+/// Ground a character stands on
+#[derive(Clone, Copy, Debug)]
+pub struct GroundHit {
+    pub entity: Entity,
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl GroundHit {
+    pub fn is_walkable(&self) -> bool {
+        is_walkable(self.normal)
+    }
+}
+
+fn is_walkable(normal: Vec3) -> bool {
+    normal.angle_between(Vec3::Y) <= MAX_SLOPE_ANGLE
+}
+
+/// The parts of the world that character movement depends on.
+///
+/// Characters are kinematic bodies moved by shape casts against the world. The
+/// result only depends on replicated state, so it is the same on the server and
+/// when the client replays ticks during a rollback
+#[derive(SystemParam)]
+pub struct CharacterEnvironment<'w, 's> {
+    pub time: Res<'w, Time>,
+    gravity: Res<'w, Gravity>,
+    spatial_query: SpatialQuery<'w, 's>,
+    surfaces: Query<'w, 's, (&'static block::Surface, Option<&'static Rotation>)>,
+}
+
+/// What gets cast for one character
+struct Body<'a> {
+    collider: &'a Collider,
+    filter: SpatialQueryFilter,
+}
+
+impl<'a> Body<'a> {
+    fn new(character: &CharacterQueryItem<'a>) -> Self {
+        let mut filter = SpatialQueryFilter::from_excluded_entities([character.entity]);
+        if let Some(collision_layers) = character.collision_layers {
+            filter = filter.with_mask(collision_layers.filters);
+        }
+
+        Self {
+            collider: character.collider,
+            filter,
+        }
+    }
+}
+
+impl CharacterEnvironment<'_, '_> {
+    /// Moves the character for one tick, walking towards `move_dir` and jumping
+    /// when the jump button was pressed shortly before or after leaving the ground
+    pub fn move_character(
+        &self,
+        character: &mut CharacterQueryItem,
+        move_dir: Vec3,
+        jump: JumpInput,
+    ) {
+        let delta_secs = self.time.delta_secs();
+        if delta_secs <= 0.0 {
+            return;
+        }
+
+        let body = Body::new(character);
+        let start = character.position.0;
+
+        let ground = self
+            .probe_ground(&body, start)
+            .filter(GroundHit::is_walkable);
+        let surface = ground.map_or_else(block::Surface::default, |ground| {
+            self.surface(ground.entity)
+        });
+
+        let velocity = character.linear_velocity.0;
+        let horizontal = walk_velocity(
+            Vec3::new(velocity.x, 0.0, velocity.z),
+            move_dir,
+            &surface,
+            delta_secs,
+        );
+
+        let jumping = update_jump(&mut character.jump, ground.is_some(), jump);
+        let velocity = if jumping {
+            horizontal + Vec3::Y * JUMP_SPEED
+        } else if let Some(ground) = ground {
+            // Walk along slopes at full speed instead of into them
+            horizontal
+                .reject_from_normalized(ground.normal)
+                .normalize_or_zero()
+                * horizontal.length()
+        } else {
+            let mut vertical = velocity.y;
+            if character.jump.rising && (!jump.pressed || vertical <= 0.0) {
+                character.jump.rising = false;
+                if vertical > 0.0 {
+                    vertical *= JUMP_RELEASE_FACTOR;
+                }
+            }
+            horizontal + Vec3::Y * vertical + self.gravity.0 * delta_secs
+        };
+
+        let grounded = ground.is_some() && !jumping;
+        let mut position = self.move_and_slide(&body, start, velocity * delta_secs, grounded);
+        if grounded {
+            position = self.snap_to_ground(&body, position);
+        }
+
+        // The displacement becomes the body's velocity, so the physics step moves
+        // it exactly where the casts allowed and the next tick keeps its momentum
+        character.linear_velocity.0 = (position - start) / delta_secs;
+    }
+
+    fn probe_ground(&self, body: &Body, position: Vec3) -> Option<GroundHit> {
+        self.cast(body, position, Dir3::NEG_Y, GROUND_PROBE_DISTANCE)
+            .map(|hit| GroundHit {
+                entity: hit.entity,
+                normal: -hit.normal2,
+                distance: hit.distance,
+            })
+    }
+
+    /// Surface of the ground with its conveyor velocity in world space, ground
+    /// without one like the floor behaves as the default surface
+    pub fn surface(&self, ground: Entity) -> block::Surface {
+        let Ok((surface, rotation)) = self.surfaces.get(ground) else {
+            return block::Surface::default();
+        };
+
+        block::Surface {
+            conveyor: rotation.map_or(surface.conveyor, |rotation| rotation.0 * surface.conveyor),
+            ..*surface
+        }
+    }
+
+    // Capsules are symmetric around their axis and characters only turn around
+    // it, so the cast ignores their rotation
+    fn cast(
+        &self,
+        body: &Body,
+        origin: Vec3,
+        direction: Dir3,
+        max_distance: f32,
+    ) -> Option<ShapeHitData> {
+        self.spatial_query.cast_shape(
+            body.collider,
+            origin,
+            Quat::IDENTITY,
+            direction,
+            &ShapeCastConfig::from_max_distance(max_distance),
+            &body.filter,
+        )
+    }
+
+    /// Distance `body` can travel from `origin` before touching something
+    fn clearance(&self, body: &Body, origin: Vec3, direction: Dir3, distance: f32) -> f32 {
+        self.cast(body, origin, direction, distance + SKIN_WIDTH)
+            .map_or(distance, |hit| (hit.distance - SKIN_WIDTH).max(0.0))
+    }
+
+    fn move_and_slide(
+        &self,
+        body: &Body,
+        mut position: Vec3,
+        mut motion: Vec3,
+        grounded: bool,
+    ) -> Vec3 {
+        for _ in 0..MAX_SLIDES {
+            let Ok((direction, distance)) = Dir3::new_and_length(motion) else {
+                break;
+            };
+            let Some(hit) = self.cast(body, position, direction, distance + SKIN_WIDTH) else {
+                return position + motion;
+            };
+
+            let travel = (hit.distance - SKIN_WIDTH).clamp(0.0, distance);
+            position += direction * travel;
+            motion = direction * (distance - travel);
+
+            let mut normal = -hit.normal2;
+            if grounded && !is_walkable(normal) {
+                if let Some(stepped) = self.step_up(body, position, motion) {
+                    return stepped;
+                }
+                // Walls and steep slopes don't lift grounded characters
+                normal = Vec3::new(normal.x, 0.0, normal.z).normalize_or_zero();
+                if normal == Vec3::ZERO {
+                    break;
+                }
+            }
+
+            motion = motion.reject_from_normalized(normal);
+        }
+
+        position
+    }
+
+    /// Position after walking onto a ledge up to [`STEP_HEIGHT`] high, if the
+    /// obstacle in front of `position` is one
+    fn step_up(&self, body: &Body, position: Vec3, motion: Vec3) -> Option<Vec3> {
+        let (direction, distance) =
+            Dir3::new_and_length(Vec3::new(motion.x, 0.0, motion.z)).ok()?;
+
+        let raised = position + Vec3::Y * self.clearance(body, position, Dir3::Y, STEP_HEIGHT);
+
+        // The ground is checked a radius further, where the capsule would rest on
+        // top of the ledge rather than on its edge
+        let reach = self.clearance(
+            body,
+            raised,
+            direction,
+            distance + character::CHARACTER_CAPSULE_RADIUS,
+        );
+        if reach <= SKIN_WIDTH {
+            return None;
+        }
+        let landing = self.cast(
+            body,
+            raised + direction * reach,
+            Dir3::NEG_Y,
+            STEP_HEIGHT + SKIN_WIDTH,
+        )?;
+        if !is_walkable(-landing.normal2) {
+            return None;
+        }
+        let top = raised.y - (landing.distance - SKIN_WIDTH).max(0.0);
+        if top <= position.y + SKIN_WIDTH {
+            return None;
+        }
+
+        let advanced = raised + direction * distance.min(reach);
+        let lowered = self.clearance(body, advanced, Dir3::NEG_Y, advanced.y - top);
+        Some(advanced - Vec3::Y * lowered)
+    }
+
+    fn snap_to_ground(&self, body: &Body, position: Vec3) -> Vec3 {
+        match self.cast(body, position, Dir3::NEG_Y, SNAP_DISTANCE + SKIN_WIDTH) {
+            Some(hit) if is_walkable(-hit.normal2) => {
+                position - Vec3::Y * (hit.distance - SKIN_WIDTH).max(0.0)
+            }
+            _ => position,
+        }
+    }
+}
+
+/// Advances the jump timing by a tick, returns whether the character jumps
+fn update_jump(state: &mut character::JumpState, grounded: bool, input: JumpInput) -> bool {
+    if grounded {
+        state.ticks_since_grounded = 0;
+        state.rising = false;
+    } else {
+        state.ticks_since_grounded = state.ticks_since_grounded.saturating_add(1);
+    }
+    state.buffered_jump = if input.just_pressed {
+        Some(0)
+    } else {
+        state
+            .buffered_jump
+            .map(|ticks| ticks + 1)
+            .filter(|ticks| *ticks <= JUMP_BUFFER_TICKS)
+    };
+
+    let jumping = state.buffered_jump.is_some() && state.ticks_since_grounded <= COYOTE_TICKS;
+    if jumping {
+        // Neither the press nor the ground can be used for a second jump
+        state.buffered_jump = None;
+        state.ticks_since_grounded = u8::MAX;
+        state.rising = true;
+    }
+    jumping
+}
+
+/// Horizontal velocity after one tick of walking towards `move_dir` on `surface`,
+/// whose conveyor velocity is in world space
+pub fn walk_velocity(
+    ground_linear_velocity: Vec3,
+    move_dir: Vec3,
+    surface: &block::Surface,
+    delta_secs: f32,
+) -> Vec3 {
+    let desired_ground_linear_velocity = move_dir * MAX_SPEED * surface.speed + surface.conveyor;
+
+    // Speeding up depends on the grip of the surface, stopping on its friction
+    let acceleration = if move_dir == Vec3::ZERO {
+        MAX_ACCELERATION * surface.friction
+    } else {
+        MAX_ACCELERATION * surface.acceleration
+    };
+
+    ground_linear_velocity.move_towards(desired_ground_linear_velocity, acceleration * delta_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use reclipsis_assets::{health, inventory};
+
+    use super::*;
+    use crate::{
+        CharacterQuery, FIXED_TIMESTEP_HZ,
+        test_utils::{run_ticks, simulation},
+    };
+
+    const DELTA_SECS: f32 = 1.0 / FIXED_TIMESTEP_HZ as f32;
+
+    const PRESS: JumpInput = JumpInput {
+        just_pressed: true,
+        pressed: true,
+    };
+    const RELEASED: JumpInput = JumpInput {
+        just_pressed: false,
+        pressed: false,
+    };
+
+    #[test]
+    fn walking_reaches_the_surface_top_speed() {
+        let mud = block::Surface {
+            speed: 0.4,
+            ..default()
+        };
+
+        let mut velocity = Vec3::ZERO;
+        for _ in 0..FIXED_TIMESTEP_HZ as usize {
+            velocity = walk_velocity(velocity, Vec3::X, &mud, DELTA_SECS);
+        }
+
+        assert_eq!(velocity, Vec3::X * MAX_SPEED * mud.speed);
+    }
+
+    #[test]
+    fn slippery_surfaces_stop_slower() {
+        let ice = block::Surface {
+            friction: 0.05,
+            ..default()
+        };
+        let start = Vec3::X * MAX_SPEED;
+
+        let on_ground = walk_velocity(start, Vec3::ZERO, &default(), DELTA_SECS);
+        let on_ice = walk_velocity(start, Vec3::ZERO, &ice, DELTA_SECS);
+
+        assert!(on_ice.length() > on_ground.length());
+    }
+
+    #[test]
+    fn conveyors_carry_idle_characters() {
+        let conveyor = block::Surface {
+            conveyor: Vec3::Z * 3.0,
+            ..default()
+        };
+
+        let mut velocity = Vec3::ZERO;
+        for _ in 0..FIXED_TIMESTEP_HZ as usize {
+            velocity = walk_velocity(velocity, Vec3::ZERO, &conveyor, DELTA_SECS);
+        }
+
+        assert_eq!(velocity, conveyor.conveyor);
+    }
+
+    #[test]
+    fn jumps_shortly_after_leaving_the_ground() {
+        let mut state = character::JumpState::default();
+        assert!(!update_jump(&mut state, true, RELEASED));
+
+        for _ in 0..COYOTE_TICKS - 1 {
+            assert!(!update_jump(&mut state, false, RELEASED));
+        }
+        assert!(update_jump(&mut state, false, PRESS));
+        assert!(state.rising);
+
+        // Only once
+        assert!(!update_jump(&mut state, false, PRESS));
+    }
+
+    #[test]
+    fn no_jump_long_after_leaving_the_ground() {
+        let mut state = character::JumpState::default();
+        update_jump(&mut state, true, RELEASED);
+
+        for _ in 0..COYOTE_TICKS {
+            update_jump(&mut state, false, RELEASED);
+        }
+        assert!(!update_jump(&mut state, false, PRESS));
+    }
+
+    #[test]
+    fn presses_before_landing_are_buffered() {
+        let mut state = character::JumpState::default();
+        assert!(!update_jump(&mut state, false, PRESS));
+
+        for _ in 0..JUMP_BUFFER_TICKS - 1 {
+            assert!(!update_jump(&mut state, false, RELEASED));
+        }
+        assert!(update_jump(&mut state, true, RELEASED));
+
+        // Expired presses are dropped
+        let mut state = character::JumpState::default();
+        update_jump(&mut state, false, PRESS);
+        for _ in 0..JUMP_BUFFER_TICKS {
+            update_jump(&mut state, false, RELEASED);
+        }
+        assert!(!update_jump(&mut state, true, RELEASED));
+    }
+
+    #[test]
+    fn slopes_up_to_the_limit_are_walkable() {
+        let slope = |degrees: f32| Quat::from_rotation_x(degrees.to_radians()) * Vec3::Y;
+
+        assert!(is_walkable(Vec3::Y));
+        assert!(is_walkable(slope(45.0)));
+        assert!(!is_walkable(slope(60.0)));
+        assert!(!is_walkable(Vec3::X));
+    }
+
+    // Height of a grounded character above the ground it stands on
+    const STAND_HEIGHT: f32 = character::CHARACTER_CAPSULE_HEIGHT / 2.0
+        + character::CHARACTER_CAPSULE_RADIUS
+        + SKIN_WIDTH;
+
+    #[derive(Resource)]
+    struct Walk {
+        move_dir: Vec3,
+        // Whether the character stood on walkable ground at the start of each tick
+        grounded: Vec<bool>,
+    }
+
+    fn walk(
+        environment: CharacterEnvironment,
+        mut walk: ResMut<Walk>,
+        mut characters: Query<CharacterQuery>,
+    ) {
+        for mut character in &mut characters {
+            let body = Body::new(&character);
+            let grounded = environment
+                .probe_ground(&body, character.position.0)
+                .is_some_and(|ground| ground.is_walkable());
+            walk.grounded.push(grounded);
+
+            environment.move_character(&mut character, walk.move_dir, JumpInput::default());
+        }
+    }
+
+    fn spawn_static(app: &mut App, size: Vec3, position: Vec3, rotation: Quat) {
+        app.world_mut().spawn((
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y, size.z),
+            Transform::from_translation(position).with_rotation(rotation),
+            Position(position),
+            Rotation(rotation),
+        ));
+    }
+
+    /// Character walking towards `move_dir` through the world `build` spawns
+    fn walking(move_dir: Vec3, start: Vec3, build: impl FnOnce(&mut App)) -> (App, Entity) {
+        let mut app = simulation();
+        build(&mut app);
+        // Lets the spatial query pick up the world before the character moves
+        run_ticks(&mut app, 1);
+
+        app.insert_resource(Walk {
+            move_dir,
+            grounded: Vec::new(),
+        })
+        .add_systems(FixedUpdate, walk);
+        let character = app
+            .world_mut()
+            .spawn((
+                character::CharacterPhysicsBundle::default(),
+                Transform::from_translation(start),
+                Position(start),
+                character::JumpState::default(),
+                inventory::Inventory::default(),
+                inventory::EquippedSlot::default(),
+                inventory::ItemCooldowns::default(),
+                health::Health::default(),
+            ))
+            .id();
+
+        (app, character)
+    }
+
+    fn flat_ground(app: &mut App) {
+        spawn_static(
+            app,
+            Vec3::new(40.0, 1.0, 40.0),
+            Vec3::new(0.0, -0.5, 0.0),
+            Quat::IDENTITY,
+        );
+    }
+
+    fn position(app: &App, character: Entity) -> Vec3 {
+        app.world().get::<Position>(character).unwrap().0
+    }
+
+    #[test]
+    fn walks_onto_low_ledges() {
+        let ledge_height = 0.3;
+        let (mut app, character) = walking(Vec3::X, Vec3::Y * STAND_HEIGHT, |app| {
+            flat_ground(app);
+            spawn_static(
+                app,
+                Vec3::new(4.0, ledge_height, 4.0),
+                Vec3::new(3.5, ledge_height / 2.0, 0.0),
+                Quat::IDENTITY,
+            );
+        });
+
+        run_ticks(&mut app, 60);
+
+        let position = position(&app, character);
+        assert!(position.x > 2.0, "stopped at {position}");
+        assert!(
+            (position.y - (ledge_height + STAND_HEIGHT)).abs() < 0.05,
+            "ended up at {position}"
+        );
+    }
+
+    #[test]
+    fn blocks_take_a_jump() {
+        let (mut app, character) = walking(Vec3::X, Vec3::Y * STAND_HEIGHT, |app| {
+            flat_ground(app);
+            spawn_static(
+                app,
+                block::CELL_SIZE,
+                Vec3::new(2.0, block::BLOCK_HEIGHT / 2.0, 0.0),
+                Quat::IDENTITY,
+            );
+        });
+
+        run_ticks(&mut app, 60);
+
+        // Resting against the block's face at x 1.5
+        let position = position(&app, character);
+        let touching = 1.5 - character::CHARACTER_CAPSULE_RADIUS;
+        assert!(
+            position.x <= touching && position.x > touching - 0.1,
+            "stopped at {position}"
+        );
+        assert!(
+            (position.y - STAND_HEIGHT).abs() < 0.02,
+            "ended up at {position}"
+        );
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let move_dir = Vec3::new(1.0, 0.0, -1.0).normalize();
+        let (mut app, character) = walking(move_dir, Vec3::Y * STAND_HEIGHT, |app| {
+            flat_ground(app);
+            // Its face is at z -1
+            spawn_static(
+                app,
+                Vec3::new(20.0, 3.0, 1.0),
+                Vec3::new(0.0, 1.5, -1.5),
+                Quat::IDENTITY,
+            );
+        });
+
+        run_ticks(&mut app, 60);
+
+        let position = position(&app, character);
+        assert!(position.x > 2.0, "got stuck at {position}");
+        assert!(
+            position.z >= -1.0 + character::CHARACTER_CAPSULE_RADIUS,
+            "went into the wall at {position}"
+        );
+        assert!(
+            (position.y - STAND_HEIGHT).abs() < 0.02,
+            "ended up at {position}"
+        );
+    }
+
+    #[test]
+    fn stays_on_the_ground_walking_down_slopes() {
+        let (mut app, character) = walking(Vec3::X, Vec3::new(-1.0, STAND_HEIGHT, 0.0), |app| {
+            // Flat up to x 0, then going down at 30 degrees
+            spawn_static(
+                app,
+                Vec3::new(10.0, 1.0, 10.0),
+                Vec3::new(-5.0, -0.5, 0.0),
+                Quat::IDENTITY,
+            );
+            let rotation = Quat::from_rotation_z(-30.0_f32.to_radians());
+            let top_corner = rotation * Vec3::new(-5.0, 0.5, 0.0);
+            spawn_static(app, Vec3::new(10.0, 1.0, 10.0), -top_corner, rotation);
+        });
+
+        run_ticks(&mut app, 40);
+
+        let walk = app.world().resource::<Walk>();
+        assert!(
+            walk.grounded.iter().all(|grounded| *grounded),
+            "left the ground: {:?}",
+            walk.grounded
+        );
+        let position = position(&app, character);
+        assert!(
+            position.x > 1.0 && position.y < STAND_HEIGHT - 0.5,
+            "ended up at {position}"
+        );
+    }
+}
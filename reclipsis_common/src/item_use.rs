@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use reclipsis_assets::{
+    inventory::ItemId,
+    item::{Item, ItemKind, ItemRegistry},
+};
+
+use crate::{CharacterQueryItem, FIXED_TIMESTEP_HZ, protocol::CharacterAction};
+
+pub struct ItemUsePlugin;
+
+impl Plugin for ItemUsePlugin {
+    fn build(&self, app: &mut App) {
+        // Placeable items need a target cell the inputs don't carry, they are
+        // used through `protocol::BuildRequest` instead
+        let mut behaviors = ItemBehaviors::default();
+        behaviors
+            .register(BehaviorKind::Weapon, WeaponBehavior)
+            .register(BehaviorKind::Consumable, ConsumableBehavior);
+
+        app.insert_resource(behaviors).add_event::<ItemUsed>();
+    }
+}
+
+/// Items sharing a kind of behavior, several item kinds can map to one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BehaviorKind {
+    Weapon,
+    Consumable,
+    Placeable,
+}
+
+impl From<&ItemKind> for BehaviorKind {
+    fn from(kind: &ItemKind) -> Self {
+        match kind {
+            ItemKind::Melee { .. } | ItemKind::Ranged { .. } => Self::Weapon,
+            ItemKind::Consumable { .. } => Self::Consumable,
+            ItemKind::Block { .. } => Self::Placeable,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UseAction {
+    Primary,
+    Secondary,
+}
+
+/// Returned by `use_equipped_item`, for effects resolved outside the simulation
+/// like the server's hit detection
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct ItemUsed {
+    pub character: Entity,
+    pub item: ItemId,
+    pub slot: usize,
+    pub action: UseAction,
+}
+
+/// The equipped item being used by a character
+pub struct ItemUse<'a, 'w> {
+    pub action: UseAction,
+    pub item: &'a Item,
+    pub slot: usize,
+    pub character: &'a mut CharacterQueryItem<'w>,
+}
+
+/// Runs as part of the simulation on both the server and the predicting client,
+/// so it must only depend on the character and its inputs
+pub trait ItemBehavior: Send + Sync + 'static {
+    /// Returns whether the item was used, which starts its cooldown
+    fn use_item(&self, item_use: &mut ItemUse<'_, '_>) -> bool;
+}
+
+#[derive(Resource, Default)]
+pub struct ItemBehaviors(HashMap<BehaviorKind, Box<dyn ItemBehavior>>);
+
+impl ItemBehaviors {
+    /// Replaces the behavior of `kind`
+    pub fn register(&mut self, kind: BehaviorKind, behavior: impl ItemBehavior) -> &mut Self {
+        self.0.insert(kind, Box::new(behavior));
+        self
+    }
+
+    pub fn get(&self, kind: BehaviorKind) -> Option<&dyn ItemBehavior> {
+        self.0.get(&kind).map(Box::as_ref)
+    }
+}
+
+// Only the server decides what an attack hits, from the returned `ItemUsed`
+struct WeaponBehavior;
+
+impl ItemBehavior for WeaponBehavior {
+    fn use_item(&self, item_use: &mut ItemUse<'_, '_>) -> bool {
+        item_use.action == UseAction::Primary
+    }
+}
+
+// The server takes the item out of the inventory when it handles the returned
+// `ItemUsed`. The inventory isn't rolled back, so doing it here would consume it
+// again on every replay of the tick
+struct ConsumableBehavior;
+
+impl ItemBehavior for ConsumableBehavior {
+    fn use_item(&self, item_use: &mut ItemUse<'_, '_>) -> bool {
+        let ItemKind::Consumable { heal } = item_use.item.kind else {
+            return false;
+        };
+        if item_use.action != UseAction::Primary {
+            return false;
+        }
+        // Keep the item when there is nothing to heal
+        let health = &item_use.character.health;
+        if health.current >= health.max {
+            return false;
+        }
+
+        item_use.character.health.heal(heal);
+        true
+    }
+}
+
+pub fn cooldown_ticks(cooldown_secs: f32) -> u32 {
+    (cooldown_secs * FIXED_TIMESTEP_HZ as f32).ceil() as u32
+}
+
+/// Counts down item cooldowns and uses the equipped item, call it every tick
+/// next to `apply_character_action`
+pub fn use_equipped_item(
+    registry: &ItemRegistry,
+    behaviors: &ItemBehaviors,
+    action_state: &ActionState<CharacterAction>,
+    character: &mut CharacterQueryItem,
+) -> Option<ItemUsed> {
+    // Through a shared borrow first, so empty cooldowns aren't marked changed and
+    // replicated every tick
+    if !character.cooldowns.is_empty() {
+        character.cooldowns.tick();
+    }
+    if character.dead {
+        return None;
+    }
+
+    let action = if action_state.just_pressed(&CharacterAction::UsePrimary) {
+        UseAction::Primary
+    } else if action_state.just_pressed(&CharacterAction::UseSecondary) {
+        UseAction::Secondary
+    } else {
+        return None;
+    };
+
+    let slot = character.equipped_slot.0?;
+    let item = character
+        .inventory
+        .get(slot)
+        .and_then(|stack| registry.get(stack.id))?;
+    if !character.cooldowns.is_ready(item.id) {
+        return None;
+    }
+    let behavior = behaviors.get(BehaviorKind::from(&item.kind))?;
+
+    let used = behavior.use_item(&mut ItemUse {
+        action,
+        item,
+        slot,
+        character: &mut *character,
+    });
+    if !used {
+        return None;
+    }
+
+    character
+        .cooldowns
+        .start(item.id, cooldown_ticks(item.kind.cooldown_secs()));
+    Some(ItemUsed {
+        character: character.entity,
+        item: item.id,
+        slot,
+        action,
+    })
+}
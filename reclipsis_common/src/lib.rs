@@ -1,19 +1,43 @@
 use avian3d::prelude::*;
 use bevy::{ecs::query::QueryData, prelude::*};
 use leafwing_input_manager::prelude::*;
-use reclipsis_assets::{character, inventory};
+use reclipsis_assets::{block, character, definitions::RegistryPlugin, health, inventory, item};
 
-use crate::protocol::CharacterAction;
+use crate::{
+    character_controller::{CharacterEnvironment, JumpInput},
+    protocol::CharacterAction,
+};
 
 pub const FIXED_TIMESTEP_HZ: f64 = 60.0;
 
+pub const MAX_SPEED: f32 = 5.0;
+pub const MAX_ACCELERATION: f32 = 25.0;
+pub const JUMP_SPEED: f32 = 5.5;
+
+pub mod building;
+pub mod character_controller;
+pub mod item_use;
+pub mod pickup;
+pub mod projectile;
 pub mod protocol;
+#[cfg(test)]
+mod test_utils;
+pub mod voxel;
 
 pub struct SharedPlugin;
 
 impl Plugin for SharedPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(protocol::ProtocolPlugin).add_plugins(
+        app.add_plugins((
+            protocol::ProtocolPlugin,
+            RegistryPlugin::<block::BlockRegistry>::default(),
+            RegistryPlugin::<item::ItemRegistry>::default(),
+            item_use::ItemUsePlugin,
+            pickup::PickupPlugin,
+            projectile::ProjectilePlugin,
+            voxel::VoxelPlugin,
+        ))
+        .add_plugins(
             PhysicsPlugins::default()
                 .build()
                 .disable::<PhysicsInterpolationPlugin>(),
@@ -24,48 +48,35 @@ impl Plugin for SharedPlugin {
 #[derive(QueryData)]
 #[query_data(mutable, derive(Debug))]
 pub struct CharacterQuery {
-    pub external_force: &'static mut ExternalForce,
-    pub external_impulse: &'static mut ExternalImpulse,
-    pub linear_velocity: &'static LinearVelocity,
-    pub mass: &'static ComputedMass,
+    pub position: &'static Position,
+    pub linear_velocity: &'static mut LinearVelocity,
+    pub collider: &'static Collider,
+    pub jump: &'static mut character::JumpState,
     pub transform: &'static mut Transform,
     pub entity: Entity,
     pub inventory: &'static mut inventory::Inventory,
+    pub equipped_slot: &'static mut inventory::EquippedSlot,
+    pub cooldowns: &'static mut inventory::ItemCooldowns,
+    pub health: &'static mut health::Health,
+    pub dead: Has<health::Dead>,
+    // Separates characters in different instances hosted by the same server
+    pub collision_layers: Option<&'static CollisionLayers>,
+}
+
+/// Value of the `Rotate` action that keeps a character facing along `rotation`
+pub fn rotate_value(rotation: Quat) -> f32 {
+    rotation.to_euler(EulerRot::YXZ).0
 }
 
 pub fn apply_character_action(
-    time: &Res<Time>,
-    spatial_query: &SpatialQuery,
+    environment: &CharacterEnvironment,
     action_state: &ActionState<CharacterAction>,
     character: &mut CharacterQueryItem,
 ) {
-    const MAX_SPEED: f32 = 5.0;
-    const MAX_ACCELERATION: f32 = 25.0;
-
-    let max_velocity_delta_per_tick = MAX_ACCELERATION * time.delta_secs();
-
-    if action_state.just_pressed(&CharacterAction::Jump) {
-        let ray_cast_origin = character.transform.translation
-            + Vec3::new(
-                0.0,
-                -character::CHARACTER_CAPSULE_HEIGHT / 2.0 - character::CHARACTER_CAPSULE_RADIUS,
-                0.0,
-            );
-
-        if spatial_query
-            .cast_ray(
-                ray_cast_origin,
-                Dir3::NEG_Y,
-                0.01,
-                true,
-                &SpatialQueryFilter::from_excluded_entities([character.entity]),
-            )
-            .is_some()
-        {
-            character
-                .external_impulse
-                .apply_impulse(Vec3::new(0.0, 5.0, 0.0));
-        }
+    // Dead characters ignore their inputs until they respawn, but still fall
+    if character.dead {
+        environment.move_character(character, Vec3::ZERO, JumpInput::default());
+        return;
     }
 
     // Rotate character
@@ -79,37 +90,22 @@ pub fn apply_character_action(
     let move_dir = Vec3::new(move_dir.x, 0.0, -move_dir.y);
 
     let local_move_dir = character.transform.rotation * move_dir;
-
-    let ground_linear_velocity = Vec3::new(
-        character.linear_velocity.x,
-        0.0,
-        character.linear_velocity.z,
-    );
-
-    let desired_ground_linear_velocity = local_move_dir * MAX_SPEED;
-
-    let new_ground_linear_velocity = ground_linear_velocity
-        .move_towards(desired_ground_linear_velocity, max_velocity_delta_per_tick);
-
-    let required_acceleration =
-        (new_ground_linear_velocity - ground_linear_velocity) / time.delta_secs();
-
-    character
-        .external_force
-        .apply_force(required_acceleration * character.mass.value());
-
-    // Equip item
-    let slot = action_state
-        .value(&CharacterAction::Equip)
-        .round()
-        .clamp(0.0, 9.0) as u8;
-
-    match slot {
-        0 => character.inventory.equipped_item = None,
-        _ => {
-            if let Some(item) = character.inventory.inventory.get(&slot) {
-                character.inventory.equipped_item = Some(*item);
-            }
+    let jump = JumpInput {
+        just_pressed: action_state.just_pressed(&CharacterAction::Jump),
+        pressed: action_state.pressed(&CharacterAction::Jump),
+    };
+    environment.move_character(character, local_move_dir, jump);
+
+    // Equip item, only on the tick the input is pressed so rollbacks replay it once
+    for slot in 0..inventory::HOTBAR_SLOTS {
+        if action_state.just_pressed(&CharacterAction::EquipSlot(slot as u8)) {
+            character.equipped_slot.toggle(slot);
         }
     }
+    if action_state.just_pressed(&CharacterAction::NextItem) {
+        character.equipped_slot.cycle(1);
+    }
+    if action_state.just_pressed(&CharacterAction::PrevItem) {
+        character.equipped_slot.cycle(-1);
+    }
 }
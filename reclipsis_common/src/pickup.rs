@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use lightyear::prelude::*;
+use reclipsis_assets::{
+    inventory::{Inventory, ItemStack},
+    item::ItemRegistry,
+    world_item::PickupDelay,
+};
+
+use crate::protocol::CharacterAction;
+
+/// Characters pick up items they walk over
+pub const PICKUP_RADIUS: f32 = 1.0;
+/// Interact picks up the closest item within this distance
+pub const INTERACT_RADIUS: f32 = 2.5;
+/// Dropped items can't be picked up for this many ticks
+pub const DROP_PICKUP_DELAY_TICKS: u16 = 60;
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        // Before any pickups, so the server and the client's prediction agree on
+        // the tick an item becomes available
+        app.add_systems(FixedPreUpdate, count_down_pickup_delays);
+    }
+}
+
+// Confirmed copies on clients only take the server's values
+fn count_down_pickup_delays(mut items: Query<&mut PickupDelay, Without<Confirmed>>) {
+    for mut delay in &mut items {
+        // Items that are ready aren't marked changed again
+        if delay.0 > 0 {
+            delay.0 -= 1;
+        }
+    }
+}
+
+pub fn can_pick_up(delay: Option<&PickupDelay>) -> bool {
+    delay.is_none_or(|delay| delay.0 == 0)
+}
+
+/// Adds the items `character` picks up this tick to its inventory and returns them,
+/// out of `items` with their positions, stacks and pickup delays. Shared by the
+/// server and the client's prediction so they agree on pickups
+pub fn pick_up_items(
+    registry: &ItemRegistry,
+    action_state: &ActionState<CharacterAction>,
+    character: Vec3,
+    dead: bool,
+    inventory: &mut Inventory,
+    items: impl IntoIterator<Item = (Entity, Vec3, ItemStack, Option<&PickupDelay>)>,
+) -> Vec<Entity> {
+    if dead {
+        return Vec::new();
+    }
+
+    let mut stacks = Vec::new();
+    let mut closest: Option<(Entity, ItemStack, f32)> = None;
+
+    for (item, position, stack, delay) in items {
+        if !can_pick_up(delay) {
+            continue;
+        }
+
+        let distance = character.distance(position);
+        if distance <= PICKUP_RADIUS {
+            stacks.push((item, stack));
+        } else if distance <= INTERACT_RADIUS
+            && closest.is_none_or(|(.., closest_distance)| distance < closest_distance)
+        {
+            closest = Some((item, stack, distance));
+        }
+    }
+
+    if action_state.just_pressed(&CharacterAction::Interact) {
+        stacks.extend(closest.map(|(item, stack, _)| (item, stack)));
+    }
+
+    // Full inventories leave the item where it is
+    stacks
+        .into_iter()
+        .filter(|(_, stack)| inventory.add(registry, *stack).is_ok())
+        .map(|(item, _)| item)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use reclipsis_assets::{
+        inventory::INVENTORY_SLOTS,
+        item::{Item, ItemId, ItemKind},
+        voxel::BlockId,
+    };
+
+    use super::*;
+
+    const BLOCK: ItemId = ItemId(1);
+
+    fn registry() -> ItemRegistry {
+        ItemRegistry::new([Item {
+            id: BLOCK,
+            name: "Block".to_string(),
+            description: String::new(),
+            icon: String::new(),
+            max_stack: 64,
+            kind: ItemKind::Block { block: BlockId(1) },
+        }])
+        .unwrap()
+    }
+
+    fn item(index: u32, x: f32) -> (Entity, Vec3, ItemStack, Option<&'static PickupDelay>) {
+        (
+            Entity::from_raw(index),
+            Vec3::new(x, 0.0, 0.0),
+            ItemStack::new(BLOCK, 1),
+            None,
+        )
+    }
+
+    fn pick_up(
+        action_state: &ActionState<CharacterAction>,
+        dead: bool,
+        inventory: &mut Inventory,
+        items: impl IntoIterator<Item = (Entity, Vec3, ItemStack, Option<&'static PickupDelay>)>,
+    ) -> Vec<Entity> {
+        pick_up_items(
+            &registry(),
+            action_state,
+            Vec3::ZERO,
+            dead,
+            inventory,
+            items,
+        )
+    }
+
+    fn interacting() -> ActionState<CharacterAction> {
+        let mut action_state = ActionState::default();
+        action_state.press(&CharacterAction::Interact);
+        action_state
+    }
+
+    #[test]
+    fn walking_over_items_picks_them_up() {
+        let mut inventory = Inventory::default();
+
+        let picked_up = pick_up(
+            &ActionState::default(),
+            false,
+            &mut inventory,
+            [item(1, 0.5), item(2, PICKUP_RADIUS), item(3, 1.5)],
+        );
+
+        assert_eq!(picked_up, [Entity::from_raw(1), Entity::from_raw(2)]);
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(BLOCK, 2)));
+    }
+
+    #[test]
+    fn interact_picks_up_the_closest_item_in_reach() {
+        let items = [item(1, 2.0), item(2, 1.5), item(3, INTERACT_RADIUS + 0.1)];
+
+        let picked_up = pick_up(&interacting(), false, &mut Inventory::default(), items);
+        assert_eq!(picked_up, [Entity::from_raw(2)]);
+
+        let picked_up = pick_up(
+            &interacting(),
+            false,
+            &mut Inventory::default(),
+            [item(3, 3.0)],
+        );
+        assert!(picked_up.is_empty());
+    }
+
+    #[test]
+    fn delayed_items_are_left_alone() {
+        static WAITING: PickupDelay = PickupDelay(1);
+        static READY: PickupDelay = PickupDelay(0);
+        let (mut waiting, mut ready) = (item(1, 0.0), item(2, 0.0));
+        waiting.3 = Some(&WAITING);
+        ready.3 = Some(&READY);
+
+        let picked_up = pick_up(
+            &interacting(),
+            false,
+            &mut Inventory::default(),
+            [waiting, ready],
+        );
+
+        assert_eq!(picked_up, [Entity::from_raw(2)]);
+    }
+
+    #[test]
+    fn full_inventories_leave_items_behind() {
+        let mut inventory = Inventory::default();
+        inventory
+            .add(
+                &registry(),
+                ItemStack::new(BLOCK, INVENTORY_SLOTS as u32 * 64),
+            )
+            .unwrap();
+        let before = inventory.clone();
+
+        let picked_up = pick_up(&interacting(), false, &mut inventory, [item(1, 0.0)]);
+
+        assert!(picked_up.is_empty());
+        assert_eq!(inventory, before);
+    }
+
+    #[test]
+    fn dead_characters_pick_up_nothing() {
+        let mut inventory = Inventory::default();
+
+        let picked_up = pick_up(
+            &interacting(),
+            true,
+            &mut inventory,
+            [item(1, 0.0), item(2, 2.0)],
+        );
+
+        assert!(picked_up.is_empty());
+        assert_eq!(inventory, Inventory::default());
+    }
+}
@@ -0,0 +1,184 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use reclipsis_assets::{
+    character,
+    item::{Item, ItemKind},
+    projectile::{PROJECTILE_RADIUS, Projectile, ProjectilePhysicsBundle},
+};
+
+// Projectiles start just outside the shooter's capsule
+const LAUNCH_OFFSET: f32 = character::CHARACTER_CAPSULE_RADIUS + PROJECTILE_RADIUS + 0.1;
+// Aimed slightly upwards so they carry further
+const LAUNCH_ELEVATION: f32 = 0.1;
+
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ProjectileHit>()
+            .add_systems(FixedUpdate, detect_projectile_hits);
+    }
+}
+
+/// A projectile touched something, which may be its own shooter
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct ProjectileHit {
+    pub projectile: Entity,
+    pub target: Entity,
+}
+
+/// Projectile shot with `item` by a character at `position`. The server and the
+/// predicting client both launch it from this, so they simulate the same trajectory
+pub fn launch_projectile(item: &Item, position: Vec3, rotation: Quat) -> Option<impl Bundle> {
+    let ItemKind::Ranged {
+        projectile_speed,
+        gravity_scale,
+        ..
+    } = item.kind
+    else {
+        return None;
+    };
+
+    let forward = rotation * Vec3::NEG_Z;
+    let direction = (forward + Vec3::Y * LAUNCH_ELEVATION).normalize();
+
+    Some((
+        Name::new("Projectile"),
+        Projectile { item: item.id },
+        ProjectilePhysicsBundle::new(gravity_scale),
+        Position(position + forward * LAUNCH_OFFSET),
+        Rotation(rotation),
+        LinearVelocity(direction * projectile_speed),
+    ))
+}
+
+/// Projectile shot by the character of `shooter`, launched as by
+/// `launch_projectile`. The server and the shooter's client both spawn this on the
+/// same tick, so lightyear matches the two through `PreSpawned`
+pub fn prespawn_projectile(
+    item: &Item,
+    position: Vec3,
+    rotation: Quat,
+    collision_layers: CollisionLayers,
+    shooter: PeerId,
+) -> Option<impl Bundle> {
+    Some((
+        launch_projectile(item, position, rotation)?,
+        PreSpawned::default_with_salt(shooter.to_bits()),
+        // Anything else would collide with different bodies than the other side
+        collision_layers,
+    ))
+}
+
+pub fn detect_projectile_hits(
+    mut collisions: EventReader<CollisionStarted>,
+    projectiles: Query<(), With<Projectile>>,
+    mut hits: EventWriter<ProjectileHit>,
+) {
+    for CollisionStarted(a, b) in collisions.read() {
+        let (projectile, target) = if projectiles.contains(*a) {
+            (*a, *b)
+        } else if projectiles.contains(*b) {
+            (*b, *a)
+        } else {
+            continue;
+        };
+
+        hits.write(ProjectileHit { projectile, target });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reclipsis_assets::inventory::ItemId;
+
+    use super::*;
+    use crate::{
+        protocol::quantize,
+        test_utils::{run_ticks, simulation},
+    };
+
+    fn slingshot() -> Item {
+        Item {
+            id: ItemId(2),
+            name: "Slingshot".to_string(),
+            description: String::new(),
+            icon: String::new(),
+            max_stack: 1,
+            kind: ItemKind::Ranged {
+                damage: 15.0,
+                projectile_speed: 30.0,
+                gravity_scale: 0.5,
+                lifetime_secs: 3.0,
+                cooldown_secs: 0.8,
+            },
+        }
+    }
+
+    fn position(app: &App, entity: Entity) -> Vec3 {
+        app.world().get::<Position>(entity).unwrap().0
+    }
+
+    fn launch(app: &mut App) -> Entity {
+        let bundle = launch_projectile(
+            &slingshot(),
+            Vec3::new(1.0, 2.0, 3.0),
+            Quat::from_rotation_y(0.7),
+        )
+        .unwrap();
+        app.world_mut().spawn(bundle).id()
+    }
+
+    #[test]
+    fn projectiles_fall_with_their_gravity_scale() {
+        let mut app = simulation();
+        let projectile = launch(&mut app);
+        let start = position(&app, projectile);
+
+        run_ticks(&mut app, 60);
+
+        let velocity = app.world().get::<LinearVelocity>(projectile).unwrap().0;
+        let expected_fall = 9.81 * 0.5;
+        let launch_rise = 30.0 * LAUNCH_ELEVATION / (1.0 + LAUNCH_ELEVATION.powi(2)).sqrt();
+        assert!((launch_rise - velocity.y - expected_fall).abs() < 0.1);
+        assert!(position(&app, projectile).xz().distance(start.xz()) > 25.0);
+    }
+
+    // A client predicting from replicated state starts from quantized values, which
+    // must not drift far enough from the server to cause rollbacks
+    #[test]
+    fn resimulating_from_replicated_state_stays_in_sync() {
+        let mut server = simulation();
+        let server_projectile = launch(&mut server);
+        run_ticks(&mut server, 20);
+
+        let replicated_position = quantize::dequantize_position(quantize::quantize_position(
+            position(&server, server_projectile),
+        ));
+        let replicated_velocity = quantize::dequantize_velocity(quantize::quantize_velocity(
+            server
+                .world()
+                .get::<LinearVelocity>(server_projectile)
+                .unwrap()
+                .0,
+        ));
+
+        let mut client = simulation();
+        let client_projectile = client
+            .world_mut()
+            .spawn(launch_projectile(&slingshot(), Vec3::ZERO, Quat::from_rotation_y(0.7)).unwrap())
+            .insert((
+                Position(replicated_position),
+                LinearVelocity(replicated_velocity),
+            ))
+            .id();
+
+        run_ticks(&mut server, 40);
+        run_ticks(&mut client, 40);
+
+        let drift =
+            position(&server, server_projectile).distance(position(&client, client_projectile));
+        assert!(drift < 0.01, "drifted {drift}");
+    }
+}
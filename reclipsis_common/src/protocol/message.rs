@@ -0,0 +1,111 @@
+use bevy::math::IVec3;
+use reclipsis_assets::{
+    inventory::{Inventory, InventoryError, ItemStack},
+    item::ItemRegistry,
+    voxel::{BlockId, CompressedChunk},
+};
+use serde::{Deserialize, Serialize};
+
+pub struct ReliableChannel;
+
+/// Sent to clients waiting for a free slot on a full server
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QueueStatus {
+    // 1-based, 0 once the client has been admitted
+    pub position: u32,
+    pub length: u32,
+}
+
+/// Chat line typed by a player, lines starting with `/` are commands
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatBroadcast {
+    pub sender: String,
+    pub text: String,
+}
+
+/// Sent to clients on connect, they disconnect if their item or block registry differs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegistryChecksum {
+    pub items: u64,
+    pub blocks: u64,
+}
+
+/// Inventory change made in the UI, applied by the server and predicted by the client
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum InventoryRequest {
+    /// Moves onto an empty slot, merges onto the same item or swaps otherwise
+    Move { from: usize, to: usize },
+    /// Moves half of a stack to the first empty slot
+    Split { slot: usize },
+    /// Takes items out of the inventory and drops them into the world
+    Drop { slot: usize, count: u32 },
+}
+
+/// Block placed or removed in building mode, validated by the server and
+/// predicted by the client
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum BuildRequest {
+    /// Places one block item from `slot` into `cell`
+    Place { cell: IVec3, slot: usize },
+    /// Removes the placed block in `cell`, its item goes back into the inventory
+    Remove { cell: IVec3 },
+}
+
+/// Keeps the client's `VoxelWorld` in sync with the one of its character's instance
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum WorldUpdate {
+    /// Sent before the chunks of a newly entered instance
+    Reset {
+        allows_building: bool,
+    },
+    Chunk {
+        pos: IVec3,
+        chunk: CompressedChunk,
+    },
+    /// Blocks changed in `chunk`, by cell index within it
+    Edits {
+        chunk: IVec3,
+        edits: Vec<(u16, BlockId)>,
+    },
+}
+
+impl InventoryRequest {
+    /// Returns the dropped stack for [`InventoryRequest::Drop`]
+    pub fn apply(
+        &self,
+        inventory: &mut Inventory,
+        registry: &ItemRegistry,
+    ) -> Result<Option<ItemStack>, InventoryError> {
+        match *self {
+            Self::Move { from, to } => {
+                match (inventory.get(from).copied(), inventory.get(to).copied()) {
+                    (Some(_), None) => inventory.move_stack(from, to)?,
+                    (Some(source), Some(destination))
+                        if source.id == destination.id
+                            && registry
+                                .get(destination.id)
+                                .is_some_and(|item| destination.count < item.max_stack) =>
+                    {
+                        inventory.merge(registry, from, to)?
+                    }
+                    _ => inventory.swap(from, to)?,
+                }
+                Ok(None)
+            }
+            Self::Split { slot } => {
+                let count = inventory.get(slot).map_or(0, |stack| stack.count / 2);
+                let to = inventory
+                    .first_empty_slot()
+                    .ok_or(InventoryError::Full(count))?;
+                inventory.split(slot, to, count)?;
+                Ok(None)
+            }
+            Self::Drop { slot, count } => inventory.remove(slot, count).map(Some),
+        }
+    }
+}
@@ -9,6 +9,11 @@ use serde::{Deserialize, Serialize};
 
 use reclipsis_assets::*;
 
+pub mod message;
+pub mod quantize;
+
+pub use message::*;
+
 pub struct ProtocolPlugin;
 
 impl Plugin for ProtocolPlugin {
@@ -20,6 +25,37 @@ impl Plugin for ProtocolPlugin {
             },
         });
 
+        //
+        // Channels
+        app.add_channel::<ReliableChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        })
+        .add_direction(NetworkDirection::Bidirectional);
+
+        //
+        // Messages
+        app.add_message::<QueueStatus>()
+            .add_direction(NetworkDirection::ServerToClient);
+
+        app.add_message::<RegistryChecksum>()
+            .add_direction(NetworkDirection::ServerToClient);
+
+        app.add_message::<InventoryRequest>()
+            .add_direction(NetworkDirection::ClientToServer);
+
+        app.add_message::<BuildRequest>()
+            .add_direction(NetworkDirection::ClientToServer);
+
+        app.add_message::<WorldUpdate>()
+            .add_direction(NetworkDirection::ServerToClient);
+
+        app.add_message::<ChatMessage>()
+            .add_direction(NetworkDirection::ClientToServer);
+
+        app.add_message::<ChatBroadcast>()
+            .add_direction(NetworkDirection::ServerToClient);
+
         //
         // Objects
         app.register_component::<Name>()
@@ -29,40 +65,61 @@ impl Plugin for ProtocolPlugin {
             .add_prediction(PredictionMode::Once)
             .add_interpolation(InterpolationMode::Once);
 
+        app.register_component::<character::JumpState>()
+            .add_prediction(PredictionMode::Full);
+
         app.register_component::<floor::FloorMarker>()
             .add_prediction(PredictionMode::Once);
 
         app.register_component::<block::BlockMarker>()
             .add_prediction(PredictionMode::Once);
 
-        app.register_component::<inventory::Inventory>()
-            .add_prediction(PredictionMode::Simple);
+        app.register_component::<block::BlockKind>()
+            .add_prediction(PredictionMode::Once);
 
-        //
-        // Physics
-        app.register_component::<LinearVelocity>()
+        app.register_component::<world_item::WorldItem>()
+            .add_prediction(PredictionMode::Once);
+
+        app.register_component::<world_item::PickupDelay>()
+            .add_prediction(PredictionMode::Full);
+
+        app.register_component::<projectile::Projectile>()
+            .add_prediction(PredictionMode::Once);
+
+        // Health is damaged by the server and healed by predicted item uses
+        app.register_component::<health::Health>()
             .add_prediction(PredictionMode::Full);
 
-        app.register_component::<AngularVelocity>()
+        app.register_component::<health::Dead>()
+            .add_prediction(PredictionMode::Simple);
+
+        app.register_component::<inventory::Inventory>()
+            .add_prediction(PredictionMode::Simple);
+
+        app.register_component::<inventory::EquippedSlot>()
             .add_prediction(PredictionMode::Full);
 
-        app.register_component::<ExternalForce>()
+        app.register_component::<inventory::ItemCooldowns>()
             .add_prediction(PredictionMode::Full);
 
-        app.register_component::<ExternalImpulse>()
+        //
+        // Physics, sent quantized and partly delta compressed, see `quantize`
+        app.register_component_custom_serde::<LinearVelocity>(quantize::linear_velocity_serde())
             .add_prediction(PredictionMode::Full);
 
-        app.register_component::<ComputedMass>()
+        app.register_component_custom_serde::<AngularVelocity>(quantize::angular_velocity_serde())
             .add_prediction(PredictionMode::Full);
 
-        app.register_component::<Position>()
+        app.register_component_custom_serde::<Position>(quantize::position_serde())
+            .add_delta_compression::<quantize::PositionDelta>()
             .add_prediction(PredictionMode::Full)
             .add_should_rollback(position_should_rollback)
-            .add_linear_correction_fn()
+            .add_correction_fn(position_correction)
             .add_interpolation(InterpolationMode::Full)
             .add_linear_interpolation_fn();
 
-        app.register_component::<Rotation>()
+        app.register_component_custom_serde::<Rotation>(quantize::rotation_serde())
+            .add_delta_compression::<quantize::RotationDelta>()
             .add_prediction(PredictionMode::Full)
             .add_should_rollback(rotation_should_rollback)
             .add_linear_correction_fn()
@@ -77,9 +134,16 @@ pub enum CharacterAction {
     Move,
     Rotate,
     Jump,
+    Interact,
 
-    // Inventory
-    Equip,
+    // Inventory, equips a hotbar slot or unequips it if it is equipped
+    EquipSlot(u8),
+    NextItem,
+    PrevItem,
+
+    // Uses the equipped item, see `item_use`
+    UsePrimary,
+    UseSecondary,
 }
 
 impl Actionlike for CharacterAction {
@@ -87,9 +151,13 @@ impl Actionlike for CharacterAction {
         match self {
             Self::Move => InputControlKind::DualAxis,
             Self::Rotate => InputControlKind::Axis,
-            Self::Jump => InputControlKind::Button,
+            Self::Jump | Self::Interact => InputControlKind::Button,
 
-            Self::Equip => InputControlKind::Axis,
+            Self::EquipSlot(_)
+            | Self::NextItem
+            | Self::PrevItem
+            | Self::UsePrimary
+            | Self::UseSecondary => InputControlKind::Button,
         }
     }
 }
@@ -98,6 +166,17 @@ fn position_should_rollback(this: &Position, that: &Position) -> bool {
     (this.0 - that.0).length() >= 0.01
 }
 
+// Server side teleports like respawns snap instead of sliding the character across the map
+fn position_correction(start: &Position, other: &Position, t: f32) -> Position {
+    const TELEPORT_DISTANCE: f32 = 2.0;
+
+    if start.distance(other.0) >= TELEPORT_DISTANCE {
+        return *other;
+    }
+
+    Position(start.lerp(other.0, t))
+}
+
 fn rotation_should_rollback(this: &Rotation, that: &Rotation) -> bool {
     this.angle_between(that.0) >= 0.01
 }
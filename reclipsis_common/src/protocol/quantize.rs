@@ -0,0 +1,482 @@
+//! Compact wire formats for the replicated physics components
+//!
+//! Positions are fixed-point within [`POSITION_RANGE`], rotations use the smallest
+//! three quaternion encoding and velocities are fixed-point within [`VELOCITY_RANGE`].
+//! The error of each stays well below the rollback thresholds, so a quantized
+//! confirmed state doesn't trigger rollbacks on its own.
+//!
+//! Positions and rotations are also delta compressed against the last acked state,
+//! see [`PositionDelta`] and [`RotationDelta`].
+
+use std::{
+    f32::consts::SQRT_2,
+    io::{Read, Write},
+};
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use lightyear::{
+    prelude::*,
+    serde::{SerializationError, reader::Reader, writer::Writer},
+};
+use serde::{Deserialize, Serialize};
+
+/// Positions are clamped to `-POSITION_RANGE..=POSITION_RANGE` on every axis
+pub const POSITION_RANGE: f32 = 1024.0;
+const POSITION_BITS: u32 = 21;
+const POSITION_MAX: u64 = (1 << POSITION_BITS) - 1;
+
+const ROTATION_BITS: u32 = 10;
+const ROTATION_MAX: u32 = (1 << ROTATION_BITS) - 1;
+
+/// Linear and angular velocities are clamped to `-VELOCITY_RANGE..=VELOCITY_RANGE`
+pub const VELOCITY_RANGE: f32 = 64.0;
+
+pub fn position_serde() -> SerializeFns<Position> {
+    SerializeFns {
+        serialize: |position, writer| {
+            write_bytes(writer, &quantize_position(position.0).to_le_bytes())
+        },
+        deserialize: |reader| {
+            let bytes = read_bytes(reader)?;
+            Ok(Position(dequantize_position(u64::from_le_bytes(bytes))))
+        },
+    }
+}
+
+pub fn rotation_serde() -> SerializeFns<Rotation> {
+    SerializeFns {
+        serialize: |rotation, writer| {
+            write_bytes(writer, &quantize_rotation(rotation.0).to_le_bytes())
+        },
+        deserialize: |reader| {
+            let bytes = read_bytes(reader)?;
+            Ok(Rotation(dequantize_rotation(u32::from_le_bytes(bytes))))
+        },
+    }
+}
+
+pub fn linear_velocity_serde() -> SerializeFns<LinearVelocity> {
+    SerializeFns {
+        serialize: |velocity, writer| write_bytes(writer, &quantize_velocity(velocity.0)),
+        deserialize: |reader| Ok(LinearVelocity(dequantize_velocity(read_bytes(reader)?))),
+    }
+}
+
+pub fn angular_velocity_serde() -> SerializeFns<AngularVelocity> {
+    SerializeFns {
+        serialize: |velocity, writer| write_bytes(writer, &quantize_velocity(velocity.0)),
+        deserialize: |reader| Ok(AngularVelocity(dequantize_velocity(read_bytes(reader)?))),
+    }
+}
+
+fn write_bytes(writer: &mut Writer, bytes: &[u8]) -> Result<(), SerializationError> {
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn read_bytes<const N: usize>(reader: &mut Reader) -> Result<[u8; N], SerializationError> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn quantize(value: f32, range: f32, max: u64) -> u64 {
+    let normalized = ((value + range) / (2.0 * range)).clamp(0.0, 1.0);
+    // NaN would otherwise end up as 0, the far corner of the range
+    if normalized.is_nan() {
+        return max / 2;
+    }
+    (normalized * max as f32).round() as u64
+}
+
+fn dequantize(value: u64, range: f32, max: u64) -> f32 {
+    value as f32 / max as f32 * 2.0 * range - range
+}
+
+fn position_axes(position: Vec3) -> [u64; 3] {
+    position
+        .to_array()
+        .map(|axis| quantize(axis, POSITION_RANGE, POSITION_MAX))
+}
+
+fn position_from_axes(axes: [u64; 3]) -> Vec3 {
+    Vec3::from_array(axes.map(|axis| dequantize(axis, POSITION_RANGE, POSITION_MAX)))
+}
+
+/// Packs three 21 bit axes into 8 bytes, about a millimeter of precision
+pub fn quantize_position(position: Vec3) -> u64 {
+    let [x, y, z] = position_axes(position);
+    (x << (2 * POSITION_BITS)) | (y << POSITION_BITS) | z
+}
+
+pub fn dequantize_position(packed: u64) -> Vec3 {
+    position_from_axes([
+        (packed >> (2 * POSITION_BITS)) & POSITION_MAX,
+        (packed >> POSITION_BITS) & POSITION_MAX,
+        packed & POSITION_MAX,
+    ])
+}
+
+/// Smallest three encoding: the index of the largest component in 2 bits and the
+/// other three in 10 bits each. The largest one is recovered from the unit length.
+pub fn quantize_rotation(rotation: Quat) -> u32 {
+    let mut components = rotation.normalize().to_array();
+    let largest = (0..4)
+        .max_by(|a, b| components[*a].abs().total_cmp(&components[*b].abs()))
+        .unwrap();
+
+    // q and -q are the same rotation, so the largest component can be kept positive
+    if components[largest] < 0.0 {
+        components = components.map(|component| -component);
+    }
+
+    let mut packed = largest as u32;
+    for (index, component) in components.into_iter().enumerate() {
+        if index == largest {
+            continue;
+        }
+        // The other components can't be larger than 1/sqrt(2)
+        let quantized = quantize(component * SQRT_2, 1.0, ROTATION_MAX as u64) as u32;
+        packed = (packed << ROTATION_BITS) | quantized;
+    }
+
+    packed
+}
+
+pub fn dequantize_rotation(packed: u32) -> Quat {
+    let largest = (packed >> (3 * ROTATION_BITS)) as usize;
+
+    let mut components = [0.0; 4];
+    let mut shift = 3 * ROTATION_BITS;
+    for (index, component) in components.iter_mut().enumerate() {
+        if index == largest {
+            continue;
+        }
+        shift -= ROTATION_BITS;
+        let quantized = (packed >> shift) & ROTATION_MAX;
+        *component = dequantize(quantized as u64, 1.0, ROTATION_MAX as u64) / SQRT_2;
+    }
+
+    let sum_of_squares: f32 = components
+        .iter()
+        .map(|component| component * component)
+        .sum();
+    components[largest] = (1.0 - sum_of_squares).max(0.0).sqrt();
+
+    Quat::from_array(components).normalize()
+}
+
+/// Three 16 bit axes, 2 millimeters per second of precision
+pub fn quantize_velocity(velocity: Vec3) -> [u8; 6] {
+    let mut bytes = [0; 6];
+    for (chunk, axis) in bytes.chunks_exact_mut(2).zip(velocity.to_array()) {
+        let quantized = quantize(axis, VELOCITY_RANGE, u16::MAX as u64) as u16;
+        chunk.copy_from_slice(&quantized.to_le_bytes());
+    }
+    bytes
+}
+
+pub fn dequantize_velocity(bytes: [u8; 6]) -> Vec3 {
+    let mut axes = [0.0; 3];
+    for (axis, chunk) in axes.iter_mut().zip(bytes.chunks_exact(2)) {
+        let quantized = u16::from_le_bytes([chunk[0], chunk[1]]);
+        *axis = dequantize(quantized as u64, VELOCITY_RANGE, u16::MAX as u64);
+    }
+    Vec3::from_array(axes)
+}
+
+/// Difference between the quantized acked and current positions, see
+/// [`encode_position_delta`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionDelta(Vec<u8>);
+
+impl Diffable<PositionDelta> for Position {
+    fn base_value() -> Self {
+        Position(Vec3::ZERO)
+    }
+
+    fn diff(&self, new: &Self) -> PositionDelta {
+        let mut bytes = Vec::new();
+        encode_position_delta(self.0, new.0, &mut bytes);
+        PositionDelta(bytes)
+    }
+
+    fn apply_diff(&mut self, delta: &PositionDelta) {
+        match decode_position_delta(self.0, &mut delta.0.as_slice()) {
+            Some(position) => self.0 = position,
+            None => warn!("Ignoring invalid position delta"),
+        }
+    }
+}
+
+/// The quantized rotation if it changed since the acked state. Smallest three
+/// components don't shrink when subtracted, so only unchanged rotations are saved.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RotationDelta(Option<[u8; 4]>);
+
+impl Diffable<RotationDelta> for Rotation {
+    fn base_value() -> Self {
+        Rotation::IDENTITY
+    }
+
+    fn diff(&self, new: &Self) -> RotationDelta {
+        let rotation = quantize_rotation(new.0);
+        if rotation == quantize_rotation(self.0) {
+            return RotationDelta(None);
+        }
+        RotationDelta(Some(rotation.to_le_bytes()))
+    }
+
+    fn apply_diff(&mut self, delta: &RotationDelta) {
+        if let Some(bytes) = delta.0 {
+            self.0 = dequantize_rotation(u32::from_le_bytes(bytes));
+        }
+    }
+}
+
+/// Writes the difference between the quantized `base` and `position` as zigzag
+/// varints, a character walking for one send interval takes 6 bytes and one
+/// standing still 3
+pub fn encode_position_delta(base: Vec3, position: Vec3, out: &mut Vec<u8>) {
+    for (base, axis) in position_axes(base).into_iter().zip(position_axes(position)) {
+        let delta = axis as i64 - base as i64;
+        let mut zigzag = ((delta << 1) ^ (delta >> 63)) as u64;
+
+        loop {
+            let byte = (zigzag & 0x7f) as u8;
+            zigzag >>= 7;
+            if zigzag == 0 {
+                out.push(byte);
+                break;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+}
+
+/// Reverses [`encode_position_delta`], advancing `bytes` past the delta
+pub fn decode_position_delta(base: Vec3, bytes: &mut &[u8]) -> Option<Vec3> {
+    let mut axes = position_axes(base);
+
+    for axis in &mut axes {
+        let mut zigzag = 0u64;
+        let mut shift = 0;
+        loop {
+            let (byte, rest) = bytes.split_first()?;
+            *bytes = rest;
+            zigzag |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if shift >= 64 {
+                return None;
+            }
+        }
+
+        let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+        *axis = axis
+            .checked_add_signed(delta)
+            .filter(|axis| *axis <= POSITION_MAX)?;
+    }
+
+    Some(position_from_axes(axes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{position_should_rollback, rotation_should_rollback};
+
+    // Deterministic samples without pulling in rand
+    struct Samples(u64);
+
+    impl Samples {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn range(&mut self, range: f32) -> f32 {
+            (self.next() * 2.0 - 1.0) * range
+        }
+
+        fn vec3(&mut self, range: f32) -> Vec3 {
+            Vec3::new(self.range(range), self.range(range), self.range(range))
+        }
+
+        fn quat(&mut self) -> Quat {
+            Quat::from_array([
+                self.range(1.0),
+                self.range(1.0),
+                self.range(1.0),
+                self.range(1.0),
+            ])
+            .normalize()
+        }
+    }
+
+    #[test]
+    fn positions_stay_below_rollback_threshold() {
+        let mut samples = Samples(1);
+        let mut max_error = 0.0f32;
+
+        for _ in 0..10_000 {
+            let position = samples.vec3(POSITION_RANGE);
+            let decoded = dequantize_position(quantize_position(position));
+
+            max_error = max_error.max(position.distance(decoded));
+            assert!(!position_should_rollback(
+                &Position(position),
+                &Position(decoded)
+            ));
+        }
+
+        assert!(max_error < 0.002);
+    }
+
+    #[test]
+    fn rotations_stay_below_rollback_threshold() {
+        let mut samples = Samples(2);
+        let mut max_error = 0.0f32;
+
+        for _ in 0..10_000 {
+            let rotation = samples.quat();
+            let decoded = dequantize_rotation(quantize_rotation(rotation));
+
+            max_error = max_error.max(rotation.angle_between(decoded));
+            assert!(!rotation_should_rollback(
+                &Rotation(rotation),
+                &Rotation(decoded)
+            ));
+        }
+
+        assert!(max_error < 0.005);
+    }
+
+    #[test]
+    fn velocities_round_trip() {
+        let mut samples = Samples(3);
+        let mut max_error = 0.0f32;
+
+        for _ in 0..10_000 {
+            let velocity = samples.vec3(VELOCITY_RANGE);
+            let decoded = dequantize_velocity(quantize_velocity(velocity));
+            max_error = max_error.max(velocity.distance(decoded));
+        }
+
+        assert!(max_error < 0.005);
+    }
+
+    #[test]
+    fn encodings_are_smaller_than_the_raw_components() {
+        let position = size_of_val(&quantize_position(Vec3::ZERO));
+        let rotation = size_of_val(&quantize_rotation(Quat::IDENTITY));
+        let velocity = size_of_val(&quantize_velocity(Vec3::ZERO));
+
+        assert_eq!(position, 8);
+        assert!(position < size_of::<Vec3>());
+        assert_eq!(rotation, 4);
+        assert!(rotation < size_of::<Quat>());
+        assert_eq!(velocity, 6);
+        assert!(velocity < size_of::<Vec3>());
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        let decoded = dequantize_position(quantize_position(Vec3::new(5000.0, -5000.0, 0.0)));
+        assert!(decoded.x <= POSITION_RANGE && decoded.x > POSITION_RANGE - 0.01);
+        assert!(decoded.y >= -POSITION_RANGE && decoded.y < -POSITION_RANGE + 0.01);
+
+        let decoded = dequantize_position(quantize_position(Vec3::NAN));
+        assert!(decoded.is_finite());
+    }
+
+    #[test]
+    fn quantized_positions_quantize_to_themselves() {
+        // The receiver's acked state is the dequantized one, the sender diffs against
+        // its full precision copy, both must land on the same steps
+        let mut samples = Samples(4);
+
+        for _ in 0..10_000 {
+            let quantized = quantize_position(samples.vec3(POSITION_RANGE));
+            assert_eq!(quantize_position(dequantize_position(quantized)), quantized);
+        }
+    }
+
+    #[test]
+    fn position_deltas_are_smaller_than_absolute_positions() {
+        let mut samples = Samples(5);
+        let mut delta_bytes = 0;
+        let absolute_bytes = 1_000 * size_of::<u64>();
+        let mut max_error = 0.0f32;
+
+        // A character walking at max speed, sampled every send interval. The server
+        // diffs against its full precision acked copy, the client against its own.
+        let mut sender = Position(samples.vec3(100.0));
+        let mut receiver = Position(dequantize_position(quantize_position(sender.0)));
+        for _ in 0..1_000 {
+            let position = Position(sender.0 + samples.vec3(0.5));
+
+            let delta = sender.diff(&position);
+            delta_bytes += delta.0.len();
+            receiver.apply_diff(&delta);
+
+            max_error = max_error.max(position.distance(receiver.0));
+            assert_eq!(
+                receiver.0,
+                dequantize_position(quantize_position(position.0))
+            );
+            assert!(!position_should_rollback(&position, &receiver));
+
+            sender = position;
+        }
+
+        assert!(delta_bytes < absolute_bytes / 2 + absolute_bytes / 4);
+        assert!(max_error < 0.002);
+
+        assert_eq!(sender.diff(&sender).0.len(), 3);
+    }
+
+    #[test]
+    fn truncated_position_deltas_are_ignored() {
+        let mut delta = Position::base_value().diff(&Position(Vec3::splat(10.0)));
+        delta.0.pop();
+
+        let mut position = Position::base_value();
+        position.apply_diff(&delta);
+        assert_eq!(position.0, Vec3::ZERO);
+    }
+
+    #[test]
+    fn unchanged_rotations_are_skipped() {
+        let mut samples = Samples(6);
+
+        for _ in 0..1_000 {
+            let acked = Rotation(samples.quat());
+            let mut receiver = Rotation(dequantize_rotation(quantize_rotation(acked.0)));
+
+            // Below one quantization step nothing is sent
+            let jitter = Rotation(acked.0 * Quat::from_rotation_y(0.0001));
+            if quantize_rotation(jitter.0) == quantize_rotation(acked.0) {
+                assert_eq!(acked.diff(&jitter), RotationDelta(None));
+            }
+
+            let turned = Rotation(acked.0 * Quat::from_rotation_y(0.5));
+            let delta = acked.diff(&turned);
+            assert!(delta.0.is_some());
+
+            receiver.apply_diff(&delta);
+            assert!(!rotation_should_rollback(&turned, &receiver));
+        }
+
+        assert_eq!(
+            Rotation::IDENTITY.diff(&Rotation::IDENTITY),
+            RotationDelta(None)
+        );
+    }
+}
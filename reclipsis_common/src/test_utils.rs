@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::{prelude::*, render::mesh::MeshPlugin, scene::ScenePlugin, time::TimeUpdateStrategy};
+
+use crate::FIXED_TIMESTEP_HZ;
+
+/// Physics stepping one fixed tick per update, standing in for either side
+pub fn simulation() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        MeshPlugin,
+        ScenePlugin,
+        PhysicsPlugins::default()
+            .build()
+            .disable::<PhysicsInterpolationPlugin>(),
+    ))
+    .insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / FIXED_TIMESTEP_HZ,
+    )));
+    app.finish();
+    app.cleanup();
+    // The first update only starts the clock
+    app.update();
+    app
+}
+
+pub fn run_ticks(app: &mut App, ticks: usize) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use reclipsis_assets::{
+    block::BlockRegistry,
+    voxel::{ChunkPhysicsBundle, VoxelChunk, VoxelWorld},
+};
+
+pub struct VoxelPlugin;
+
+impl Plugin for VoxelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            sync_chunk_colliders.run_if(resource_exists::<BlockRegistry>),
+        );
+    }
+}
+
+// Chunk entities are local to each peer, both sides build the same colliders
+// from the same blocks. Observers on `VoxelChunk` add what else they need
+pub fn sync_chunk_colliders(
+    mut commands: Commands,
+    registry: Res<BlockRegistry>,
+    mut worlds: Query<(Entity, &mut VoxelWorld), Changed<VoxelWorld>>,
+    chunks: Query<(Entity, &VoxelChunk)>,
+) {
+    for (world, mut voxel_world) in &mut worlds {
+        let voxel_world = voxel_world.bypass_change_detection();
+
+        for pos in voxel_world.take_dirty() {
+            let boxes = voxel_world
+                .chunk(pos)
+                .map(|chunk| chunk.merged_boxes())
+                .unwrap_or_default();
+            let mut physics: HashMap<_, _> = registry
+                .iter()
+                .filter_map(|block_type| {
+                    ChunkPhysicsBundle::new(&boxes, block_type)
+                        .map(|physics| (block_type.id, physics))
+                })
+                .collect();
+
+            for (entity, chunk) in &chunks {
+                if chunk.world != world || chunk.pos != pos {
+                    continue;
+                }
+                match physics.remove(&chunk.block) {
+                    Some(physics) => {
+                        commands.entity(entity).insert(physics);
+                    }
+                    None => commands.entity(entity).despawn(),
+                }
+            }
+
+            for (block, physics) in physics {
+                let chunk = VoxelChunk { world, pos, block };
+                commands.spawn((
+                    Name::new(format!("Chunk {pos}")),
+                    chunk,
+                    physics,
+                    Position::new(chunk.origin()),
+                    Transform::from_translation(chunk.origin()),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::{net::SocketAddr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_MASTER_ADDR: &str = "127.0.0.1:27900";
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+pub const SERVER_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub const MAX_PACKET_SIZE: usize = 16 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerInfo {
+    pub name: String,
+    pub address: SocketAddr,
+    pub players: u32,
+    pub max_players: u32,
+    pub map: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerListing {
+    pub info: ServerInfo,
+    // Address the heartbeats came from, game servers answer pings on it
+    pub query_addr: SocketAddr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum MasterPacket {
+    // Game server -> master
+    Heartbeat(ServerInfo),
+
+    // Client -> master
+    ListRequest,
+    ListResponse(Vec<ServerListing>),
+
+    // Client -> game server
+    Ping(u64),
+    Pong(u64),
+}
+
+impl MasterPacket {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        ron::to_string(self)
+            .expect("master packets are always serializable")
+            .into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let text = std::str::from_utf8(bytes).ok()?;
+        ron::from_str(text).ok()
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    time::Instant,
+};
+
+use reclipsis_master::*;
+
+struct ServerEntry {
+    info: ServerInfo,
+    last_heartbeat: Instant,
+}
+
+fn main() -> std::io::Result<()> {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_MASTER_ADDR.to_string());
+
+    let socket = UdpSocket::bind(&addr)?;
+    socket.set_read_timeout(Some(HEARTBEAT_INTERVAL))?;
+    println!("Master server listening on {addr}");
+
+    // Keyed by the address heartbeats come from
+    let mut servers: HashMap<SocketAddr, ServerEntry> = HashMap::new();
+    let mut buf = [0; MAX_PACKET_SIZE];
+
+    loop {
+        servers.retain(|query_addr, entry| {
+            let alive = entry.last_heartbeat.elapsed() < SERVER_TIMEOUT;
+            if !alive {
+                println!("Server {:?} at {query_addr} timed out", entry.info.name);
+            }
+            alive
+        });
+
+        let (len, src) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                continue;
+            }
+            Err(err) => {
+                eprintln!("Failed to receive packet: {err}");
+                continue;
+            }
+        };
+
+        let Some(packet) = MasterPacket::from_bytes(&buf[..len]) else {
+            eprintln!("Ignoring malformed packet from {src}");
+            continue;
+        };
+
+        match packet {
+            MasterPacket::Heartbeat(info) => {
+                if !servers.contains_key(&src) {
+                    println!("Server {:?} registered from {src}", info.name);
+                }
+                servers.insert(
+                    src,
+                    ServerEntry {
+                        info,
+                        last_heartbeat: Instant::now(),
+                    },
+                );
+            }
+            MasterPacket::ListRequest => {
+                let listings = servers
+                    .iter()
+                    .map(|(query_addr, entry)| ServerListing {
+                        info: entry.info.clone(),
+                        query_addr: *query_addr,
+                    })
+                    .collect();
+
+                if let Err(err) =
+                    socket.send_to(&MasterPacket::ListResponse(listings).to_bytes(), src)
+                {
+                    eprintln!("Failed to send server list to {src}: {err}");
+                }
+            }
+            MasterPacket::Ping(nonce) => {
+                let _ = socket.send_to(&MasterPacket::Pong(nonce).to_bytes(), src);
+            }
+            MasterPacket::ListResponse(_) | MasterPacket::Pong(_) => {}
+        }
+    }
+}
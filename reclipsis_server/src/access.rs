@@ -0,0 +1,336 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs,
+    net::IpAddr,
+    path::Path,
+};
+
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_common::protocol::{QueueStatus, ReliableChannel};
+use serde::{Deserialize, Serialize};
+
+use crate::{admin::AdminCommand, config::ServerConfig};
+
+pub struct AccessPlugin;
+
+impl Plugin for AccessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConnectionQueue>()
+            .add_systems(Startup, load_access_lists)
+            .add_systems(
+                Update,
+                (handle_admin_commands, admit_from_queue, send_queue_status).chain(),
+            )
+            .add_observer(check_access)
+            .add_observer(handle_disconnected);
+    }
+}
+
+/// Marks a client that got a player slot, a character is spawned for it
+#[derive(Component, Debug)]
+pub struct Admitted;
+
+/// Clients that passed the access checks but are waiting for a free slot
+#[derive(Resource, Default, Debug)]
+pub struct ConnectionQueue(VecDeque<Entity>);
+
+#[derive(Resource, Serialize, Deserialize, Default, Debug)]
+pub struct AccessLists {
+    pub whitelist_enabled: bool,
+    pub whitelist: HashSet<u64>,
+    pub banned_ids: HashSet<u64>,
+    pub banned_ips: HashSet<IpAddr>,
+}
+
+impl AccessLists {
+    fn load(path: &Path) -> Self {
+        let Ok(text) = fs::read_to_string(path) else {
+            info!("No access lists at {path:?}, starting with empty lists");
+            return Self::default();
+        };
+
+        ron::from_str(&text).unwrap_or_else(|err| {
+            error!("Failed to parse access lists at {path:?}: {err}");
+            Self::default()
+        })
+    }
+
+    fn save(&self, path: &Path) {
+        let text = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(text) => text,
+            Err(err) => {
+                error!("Failed to serialize access lists: {err}");
+                return;
+            }
+        };
+
+        if let Err(err) = fs::write(path, text) {
+            error!("Failed to write access lists to {path:?}: {err}");
+        }
+    }
+
+    fn denial_reason(&self, client_id: u64, ip: Option<IpAddr>) -> Option<&'static str> {
+        if self.banned_ids.contains(&client_id) {
+            return Some("client id is banned");
+        }
+        if ip.is_some_and(|ip| self.banned_ips.contains(&ip)) {
+            return Some("IP address is banned");
+        }
+        if self.whitelist_enabled && !self.whitelist.contains(&client_id) {
+            return Some("client id is not whitelisted");
+        }
+        None
+    }
+}
+
+fn load_access_lists(mut commands: Commands, config: Res<ServerConfig>) {
+    commands.insert_resource(AccessLists::load(&config.access_list_path));
+}
+
+fn check_access(
+    trigger: Trigger<OnAdd, Connected>,
+    query: Query<(&RemoteId, Option<&PeerAddr>), With<ClientOf>>,
+    access_lists: Res<AccessLists>,
+    mut queue: ResMut<ConnectionQueue>,
+    mut commands: Commands,
+) {
+    let Ok((remote_id, peer_addr)) = query.get(trigger.target()) else {
+        return;
+    };
+    let client_id = remote_id.0.to_bits();
+
+    if let Some(reason) = access_lists.denial_reason(client_id, peer_addr.map(|addr| addr.0.ip())) {
+        info!("Rejecting client {client_id}: {reason}");
+        commands.trigger_targets(Disconnect, trigger.target());
+        return;
+    }
+
+    queue.0.push_back(trigger.target());
+}
+
+fn handle_disconnected(trigger: Trigger<OnAdd, Disconnected>, mut queue: ResMut<ConnectionQueue>) {
+    queue.0.retain(|entity| *entity != trigger.target());
+}
+
+fn admit_from_queue(
+    mut commands: Commands,
+    config: Res<ServerConfig>,
+    mut queue: ResMut<ConnectionQueue>,
+    admitted: Query<(), (With<Admitted>, With<Connected>)>,
+    connected: Query<(), With<Connected>>,
+) {
+    if queue.0.is_empty() {
+        return;
+    }
+
+    let mut player_count = admitted.iter().count() as u32;
+
+    while player_count < config.max_players {
+        let Some(entity) = queue.0.pop_front() else {
+            break;
+        };
+        if !connected.contains(entity) {
+            continue;
+        }
+
+        commands.entity(entity).insert(Admitted);
+        player_count += 1;
+    }
+}
+
+fn send_queue_status(
+    queue: Res<ConnectionQueue>,
+    mut senders: Query<&mut MessageSender<QueueStatus>>,
+    mut previously_queued: Local<Vec<Entity>>,
+) {
+    if !queue.is_changed() {
+        return;
+    }
+
+    // Let clients that left the queue know they are in
+    for entity in previously_queued.drain(..) {
+        if !queue.0.contains(&entity)
+            && let Ok(mut sender) = senders.get_mut(entity)
+        {
+            sender.send::<ReliableChannel>(QueueStatus {
+                position: 0,
+                length: queue.0.len() as u32,
+            });
+        }
+    }
+
+    for (index, entity) in queue.0.iter().enumerate() {
+        if let Ok(mut sender) = senders.get_mut(*entity) {
+            sender.send::<ReliableChannel>(QueueStatus {
+                position: index as u32 + 1,
+                length: queue.0.len() as u32,
+            });
+        }
+    }
+
+    previously_queued.extend(queue.0.iter().copied());
+}
+
+fn handle_admin_commands(
+    mut commands: Commands,
+    mut admin_commands: EventReader<AdminCommand>,
+    mut access_lists: ResMut<AccessLists>,
+    mut config: ResMut<ServerConfig>,
+    queue: Res<ConnectionQueue>,
+    clients: Query<
+        (Entity, &RemoteId, Option<&PeerAddr>, Has<Admitted>),
+        (With<ClientOf>, With<Connected>),
+    >,
+) {
+    let mut disconnect = |matches: &dyn Fn(u64, Option<IpAddr>) -> bool| {
+        for (entity, remote_id, peer_addr, _) in &clients {
+            if matches(remote_id.0.to_bits(), peer_addr.map(|addr| addr.0.ip())) {
+                commands.trigger_targets(Disconnect, entity);
+            }
+        }
+    };
+
+    let mut lists_changed = false;
+
+    for command in admin_commands.read() {
+        match command {
+            AdminCommand::Players => {
+                for (entity, remote_id, peer_addr, is_admitted) in &clients {
+                    let status = match queue.0.iter().position(|queued| *queued == entity) {
+                        Some(index) => format!("queued #{}", index + 1),
+                        None if is_admitted => "playing".to_string(),
+                        None => "connecting".to_string(),
+                    };
+                    info!(
+                        "Client {} ({:?}): {status}",
+                        remote_id.0.to_bits(),
+                        peer_addr.map(|addr| addr.0)
+                    );
+                }
+            }
+            AdminCommand::Kick(client_id) => disconnect(&|id, _| id == *client_id),
+            AdminCommand::Ban(client_id) => {
+                access_lists.banned_ids.insert(*client_id);
+                lists_changed = true;
+                disconnect(&|id, _| id == *client_id);
+            }
+            AdminCommand::Unban(client_id) => {
+                access_lists.banned_ids.remove(client_id);
+                lists_changed = true;
+            }
+            AdminCommand::BanIp(banned_ip) => {
+                access_lists.banned_ips.insert(*banned_ip);
+                lists_changed = true;
+                disconnect(&|_, ip| ip == Some(*banned_ip));
+            }
+            AdminCommand::UnbanIp(ip) => {
+                access_lists.banned_ips.remove(ip);
+                lists_changed = true;
+            }
+            AdminCommand::SetWhitelist(enabled) => {
+                access_lists.whitelist_enabled = *enabled;
+                lists_changed = true;
+                if *enabled {
+                    let whitelist = access_lists.whitelist.clone();
+                    disconnect(&|id, _| !whitelist.contains(&id));
+                }
+            }
+            AdminCommand::WhitelistAdd(client_id) => {
+                access_lists.whitelist.insert(*client_id);
+                lists_changed = true;
+            }
+            AdminCommand::WhitelistRemove(client_id) => {
+                access_lists.whitelist.remove(client_id);
+                lists_changed = true;
+                if access_lists.whitelist_enabled {
+                    disconnect(&|id, _| id == *client_id);
+                }
+            }
+            AdminCommand::MaxPlayers(max_players) => {
+                config.max_players = *max_players;
+                info!("Max players set to {max_players}");
+            }
+            // Handled by the console, the instance and the metrics plugins
+            AdminCommand::Help
+            | AdminCommand::Instances
+            | AdminCommand::MoveToInstance(..)
+            | AdminCommand::Bandwidth => {}
+        }
+    }
+
+    if lists_changed {
+        access_lists.save(&config.access_list_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+
+    #[test]
+    fn empty_lists_admit_everyone() {
+        let lists = AccessLists::default();
+
+        assert_eq!(lists.denial_reason(1, Some(IP)), None);
+        assert_eq!(lists.denial_reason(1, None), None);
+    }
+
+    #[test]
+    fn bans_take_precedence_over_the_whitelist() {
+        let lists = AccessLists {
+            whitelist_enabled: true,
+            whitelist: HashSet::from([1, 2]),
+            banned_ids: HashSet::from([1]),
+            banned_ips: HashSet::from([IP]),
+        };
+
+        assert_eq!(lists.denial_reason(1, None), Some("client id is banned"));
+        assert_eq!(
+            lists.denial_reason(2, Some(IP)),
+            Some("IP address is banned")
+        );
+        assert_eq!(lists.denial_reason(2, None), None);
+    }
+
+    #[test]
+    fn whitelist_only_applies_when_enabled() {
+        let mut lists = AccessLists {
+            whitelist: HashSet::from([1]),
+            ..default()
+        };
+        assert_eq!(lists.denial_reason(2, None), None);
+
+        lists.whitelist_enabled = true;
+        assert_eq!(lists.denial_reason(1, None), None);
+        assert_eq!(
+            lists.denial_reason(2, None),
+            Some("client id is not whitelisted")
+        );
+    }
+
+    #[test]
+    fn ip_bans_cover_every_client_id_from_that_address() {
+        let lists = AccessLists {
+            banned_ips: HashSet::from([IP]),
+            ..default()
+        };
+
+        assert_eq!(
+            lists.denial_reason(1, Some(IP)),
+            Some("IP address is banned")
+        );
+        assert_eq!(
+            lists.denial_reason(2, Some(IP)),
+            Some("IP address is banned")
+        );
+        assert_eq!(
+            lists.denial_reason(1, Some(IpAddr::from([10, 0, 0, 2]))),
+            None
+        );
+        // Clients without a known address can't be matched against IP bans
+        assert_eq!(lists.denial_reason(1, None), None);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    io::BufRead,
+    net::IpAddr,
+    str::FromStr,
+    sync::{
+        Mutex,
+        mpsc::{self, Receiver},
+    },
+};
+
+use bevy::prelude::*;
+
+const HELP: &str = "Commands:
+  players                     List admitted and queued clients
+  kick <client-id>            Disconnect a client
+  ban <client-id>             Ban a client id and disconnect it
+  unban <client-id>           Lift a client id ban
+  ban-ip <ip>                 Ban an IP address and disconnect its clients
+  unban-ip <ip>               Lift an IP ban
+  whitelist <on|off>          Toggle whitelist mode
+  whitelist <add|remove> <id> Edit the whitelist
+  max-players <count>         Change the player limit
+  instances                   List instances and their player counts
+  move <client-id> <instance> Move a client's character to another instance
+  bandwidth                   Show measured bandwidth sent to each client";
+
+pub struct AdminPlugin;
+
+impl Plugin for AdminPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AdminCommand>()
+            .add_systems(Startup, spawn_console)
+            .add_systems(PreUpdate, read_console.run_if(resource_exists::<Console>));
+    }
+}
+
+/// Issued from the server console
+#[derive(Event, Clone, Debug, PartialEq)]
+pub enum AdminCommand {
+    Help,
+    Players,
+    Kick(u64),
+    Ban(u64),
+    Unban(u64),
+    BanIp(IpAddr),
+    UnbanIp(IpAddr),
+    SetWhitelist(bool),
+    WhitelistAdd(u64),
+    WhitelistRemove(u64),
+    MaxPlayers(u32),
+    Instances,
+    MoveToInstance(u64, String),
+    Bandwidth,
+}
+
+impl FromStr for AdminCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+
+        let command = match args.as_slice() {
+            ["help"] => Self::Help,
+            ["players"] => Self::Players,
+            ["kick", id] => Self::Kick(parse(id)?),
+            ["ban", id] => Self::Ban(parse(id)?),
+            ["unban", id] => Self::Unban(parse(id)?),
+            ["ban-ip", ip] => Self::BanIp(parse(ip)?),
+            ["unban-ip", ip] => Self::UnbanIp(parse(ip)?),
+            ["whitelist", "on"] => Self::SetWhitelist(true),
+            ["whitelist", "off"] => Self::SetWhitelist(false),
+            ["whitelist", "add", id] => Self::WhitelistAdd(parse(id)?),
+            ["whitelist", "remove", id] => Self::WhitelistRemove(parse(id)?),
+            ["max-players", count] => Self::MaxPlayers(parse(count)?),
+            ["instances"] => Self::Instances,
+            ["move", id, instance] => Self::MoveToInstance(parse(id)?, instance.to_string()),
+            ["bandwidth"] => Self::Bandwidth,
+            _ => return Err(format!("Unknown command {line:?}, try \"help\"")),
+        };
+
+        Ok(command)
+    }
+}
+
+fn parse<T: FromStr>(arg: &str) -> Result<T, String> {
+    arg.parse().map_err(|_| format!("Invalid argument {arg:?}"))
+}
+
+#[derive(Resource)]
+struct Console(Mutex<Receiver<String>>);
+
+fn spawn_console(mut commands: Commands) {
+    let (sender, receiver) = mpsc::channel();
+
+    // Stdin blocks, so lines are read on their own thread
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    commands.insert_resource(Console(Mutex::new(receiver)));
+}
+
+fn read_console(console: Res<Console>, mut admin_commands: EventWriter<AdminCommand>) {
+    let receiver = console.0.lock().unwrap();
+
+    while let Ok(line) = receiver.try_recv() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match line.parse::<AdminCommand>() {
+            Ok(AdminCommand::Help) => info!("{HELP}"),
+            Ok(command) => {
+                admin_commands.write(command);
+            }
+            Err(err) => warn!("{err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_with_their_arguments() {
+        assert_eq!("kick 42".parse(), Ok(AdminCommand::Kick(42)));
+        assert_eq!(
+            "  ban-ip   10.0.0.1 ".parse(),
+            Ok(AdminCommand::BanIp(IpAddr::from([10, 0, 0, 1])))
+        );
+        assert_eq!(
+            "whitelist off".parse(),
+            Ok(AdminCommand::SetWhitelist(false))
+        );
+        assert_eq!(
+            "whitelist remove 7".parse(),
+            Ok(AdminCommand::WhitelistRemove(7))
+        );
+        assert_eq!(
+            "move 3 arena".parse(),
+            Ok(AdminCommand::MoveToInstance(3, "arena".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_argument_counts() {
+        for line in ["kick", "kick 1 2", "players now", "move 3", "whitelist add"] {
+            assert!(line.parse::<AdminCommand>().is_err(), "parsed {line:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_bad_arguments() {
+        assert_eq!(
+            "teleport 1".parse::<AdminCommand>(),
+            Err("Unknown command \"teleport 1\", try \"help\"".to_string())
+        );
+        assert_eq!(
+            "whitelist maybe".parse::<AdminCommand>(),
+            Err("Unknown command \"whitelist maybe\", try \"help\"".to_string())
+        );
+        assert_eq!(
+            "kick someone".parse::<AdminCommand>(),
+            Err("Invalid argument \"someone\"".to_string())
+        );
+        assert_eq!(
+            "ban-ip 300.0.0.1".parse::<AdminCommand>(),
+            Err("Invalid argument \"300.0.0.1\"".to_string())
+        );
+        assert!("max-players -1".parse::<AdminCommand>().is_err());
+    }
+}
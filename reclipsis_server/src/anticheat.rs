@@ -0,0 +1,349 @@
+use std::{collections::VecDeque, f32::consts::TAU};
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use lightyear::prelude::*;
+use reclipsis_assets::{block::BlockRegistry, character::CharacterMarker};
+use reclipsis_common::{MAX_SPEED, character_controller::STEP_HEIGHT, protocol::CharacterAction};
+
+use crate::config::ServerConfig;
+
+// Collisions can push characters a bit faster than they can walk
+const HORIZONTAL_SPEED_TOLERANCE: f32 = 1.5;
+const MAX_UPWARD_SPEED: f32 = 10.0;
+// Anything further than this in a single tick is a teleport
+const TELEPORT_DISTANCE: f32 = 2.0;
+
+const MAX_JUMPS_PER_SECOND: usize = 10;
+const MAX_EQUIPS_PER_SECOND: usize = 20;
+
+// One violation is forgiven every this many seconds
+const VIOLATION_DECAY_SECS: f32 = 10.0;
+
+pub struct AntiCheatPlugin;
+
+impl Plugin for AntiCheatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Violation>()
+            .add_systems(
+                FixedUpdate,
+                (sanitize_inputs, check_movement, handle_violations)
+                    .chain()
+                    .before(crate::handle_character_actions),
+            )
+            .add_observer(add_input_validation);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViolationKind {
+    InvalidInput,
+    InputRate,
+    Speed,
+    Teleport,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct Violation {
+    pub character: Entity,
+    pub kind: ViolationKind,
+    pub details: String,
+}
+
+/// Per character bookkeeping for validating inputs and movement
+#[derive(Component, Default, Debug)]
+pub struct InputValidation {
+    last_position: Option<Vec3>,
+    last_rotate: f32,
+    // Elapsed seconds of recent presses
+    jump_presses: VecDeque<f32>,
+    equip_changes: VecDeque<f32>,
+    violations: f32,
+}
+
+impl InputValidation {
+    /// Skips the movement check for the next tick, for server side teleports
+    pub fn reset_position(&mut self) {
+        self.last_position = None;
+    }
+}
+
+fn add_input_validation(trigger: Trigger<OnAdd, CharacterMarker>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert(InputValidation::default());
+}
+
+fn sanitize_inputs(
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &mut ActionState<CharacterAction>,
+        &mut InputValidation,
+    )>,
+    mut violations: EventWriter<Violation>,
+) {
+    let now = time.elapsed_secs();
+
+    for (character, mut action_state, mut validation) in &mut query {
+        let mut violation = |kind, details: String| {
+            violations.write(Violation {
+                character,
+                kind,
+                details,
+            });
+        };
+
+        let rotate = action_state.value(&CharacterAction::Rotate);
+        if rotate.is_finite() {
+            validation.last_rotate = rotate.rem_euclid(TAU);
+        } else {
+            violation(ViolationKind::InvalidInput, format!("rotation {rotate}"));
+        }
+        action_state.set_value(&CharacterAction::Rotate, validation.last_rotate);
+
+        let move_dir = action_state.axis_pair(&CharacterAction::Move);
+        if !move_dir.is_finite() {
+            violation(ViolationKind::InvalidInput, format!("move {move_dir}"));
+            action_state.set_axis_pair(&CharacterAction::Move, Vec2::ZERO);
+        }
+
+        // Slots outside the hotbar are ignored when applying the actions
+        let equip_actions: Vec<CharacterAction> = action_state
+            .get_just_pressed()
+            .into_iter()
+            .filter(|action| {
+                matches!(
+                    action,
+                    CharacterAction::EquipSlot(_)
+                        | CharacterAction::NextItem
+                        | CharacterAction::PrevItem
+                )
+            })
+            .collect();
+        if !equip_actions.is_empty()
+            && !record_event(&mut validation.equip_changes, now, MAX_EQUIPS_PER_SECOND)
+        {
+            violation(ViolationKind::InputRate, "equip changes".to_string());
+            for action in &equip_actions {
+                action_state.release(action);
+            }
+        }
+
+        if action_state.just_pressed(&CharacterAction::Jump)
+            && !record_event(&mut validation.jump_presses, now, MAX_JUMPS_PER_SECOND)
+        {
+            violation(ViolationKind::InputRate, "jump presses".to_string());
+            action_state.release(&CharacterAction::Jump);
+        }
+    }
+}
+
+/// Returns false if the event goes over the per second limit
+fn record_event(events: &mut VecDeque<f32>, now: f32, max_per_second: usize) -> bool {
+    while events.front().is_some_and(|time| now - time > 1.0) {
+        events.pop_front();
+    }
+
+    if events.len() >= max_per_second {
+        return false;
+    }
+
+    events.push_back(now);
+    true
+}
+
+// Positions only change through the server's physics, so anomalies point at
+// exploits of the simulation rather than forged positions
+fn check_movement(
+    time: Res<Time>,
+    block_registry: Option<Res<BlockRegistry>>,
+    mut query: Query<(Entity, &Position, &mut InputValidation)>,
+    mut violations: EventWriter<Violation>,
+) {
+    let delta_secs = time.delta_secs();
+    if delta_secs <= 0.0 {
+        return;
+    }
+
+    let max_speed = max_speed(block_registry.as_deref());
+
+    for (character, position, mut validation) in &mut query {
+        let Some(last_position) = validation.last_position.replace(position.0) else {
+            continue;
+        };
+
+        if let Some((kind, details)) =
+            movement_violation(position.0 - last_position, delta_secs, max_speed)
+        {
+            violations.write(Violation {
+                character,
+                kind,
+                details,
+            });
+        }
+    }
+}
+
+// Fast surfaces and conveyors carry characters past their walking speed
+fn max_speed(block_registry: Option<&BlockRegistry>) -> f32 {
+    block_registry
+        .iter()
+        .flat_map(|registry| registry.iter())
+        .map(|block_type| block_type.surface.top_speed(MAX_SPEED))
+        .fold(MAX_SPEED, f32::max)
+}
+
+/// Checks the displacement of a character over one tick
+fn movement_violation(
+    delta: Vec3,
+    delta_secs: f32,
+    max_speed: f32,
+) -> Option<(ViolationKind, String)> {
+    if delta.length() > TELEPORT_DISTANCE {
+        return Some((
+            ViolationKind::Teleport,
+            format!("moved {:.2} in one tick", delta.length()),
+        ));
+    }
+
+    let horizontal_speed = delta.xz().length() / delta_secs;
+    // Stepping onto a ledge lifts characters by up to a step in a single tick
+    let upward_speed = (delta.y - STEP_HEIGHT).max(0.0) / delta_secs;
+
+    (horizontal_speed > max_speed * HORIZONTAL_SPEED_TOLERANCE || upward_speed > MAX_UPWARD_SPEED)
+        .then(|| {
+            (
+                ViolationKind::Speed,
+                format!("horizontal speed {horizontal_speed:.2}, upward speed {upward_speed:.2}"),
+            )
+        })
+}
+
+fn handle_violations(
+    time: Res<Time>,
+    config: Res<ServerConfig>,
+    mut commands: Commands,
+    mut violations: EventReader<Violation>,
+    mut query: Query<(&mut InputValidation, &ControlledBy)>,
+    remote_ids: Query<&RemoteId>,
+) {
+    for (mut validation, _) in &mut query {
+        validation.violations =
+            (validation.violations - time.delta_secs() / VIOLATION_DECAY_SECS).max(0.0);
+    }
+
+    for violation in violations.read() {
+        let Ok((mut validation, controlled_by)) = query.get_mut(violation.character) else {
+            continue;
+        };
+        validation.violations += 1.0;
+
+        let client_id = remote_ids
+            .get(controlled_by.owner)
+            .map(|remote_id| remote_id.0.to_bits());
+        warn!(
+            "Client {client_id:?} violation {:?}: {}",
+            violation.kind, violation.details
+        );
+
+        if let Some(max_violations) = config.kick_after_violations
+            && validation.violations >= max_violations as f32
+        {
+            warn!("Kicking client {client_id:?} after repeated violations");
+            commands.trigger_targets(Disconnect, controlled_by.owner);
+            validation.violations = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reclipsis_assets::{
+        block::{BlockType, Surface},
+        voxel::BlockId,
+    };
+    use reclipsis_common::FIXED_TIMESTEP_HZ;
+
+    use super::*;
+
+    const DELTA_SECS: f32 = 1.0 / FIXED_TIMESTEP_HZ as f32;
+
+    fn kind(delta: Vec3, max_speed: f32) -> Option<ViolationKind> {
+        movement_violation(delta, DELTA_SECS, max_speed).map(|(kind, _)| kind)
+    }
+
+    #[test]
+    fn events_are_limited_over_a_sliding_second() {
+        let mut events = VecDeque::new();
+
+        assert!(record_event(&mut events, 0.0, 2));
+        assert!(record_event(&mut events, 0.5, 2));
+        assert!(!record_event(&mut events, 0.9, 2));
+        // The first press is still exactly a second old
+        assert!(!record_event(&mut events, 1.0, 2));
+        assert!(record_event(&mut events, 1.01, 2));
+        // Rejected events don't count towards the limit
+        assert_eq!(events, VecDeque::from([0.5, 1.01]));
+    }
+
+    #[test]
+    fn walking_and_stepping_up_are_allowed() {
+        let walked = Vec3::X * MAX_SPEED * DELTA_SECS;
+
+        assert_eq!(kind(walked, MAX_SPEED), None);
+        assert_eq!(kind(walked + Vec3::Y * STEP_HEIGHT, MAX_SPEED), None);
+        // Pushed by a collision, within the tolerance
+        assert_eq!(
+            kind(walked * HORIZONTAL_SPEED_TOLERANCE * 0.99, MAX_SPEED),
+            None
+        );
+    }
+
+    #[test]
+    fn too_fast_and_too_far_are_flagged() {
+        let walked = Vec3::X * MAX_SPEED * DELTA_SECS;
+
+        assert_eq!(
+            kind(walked * HORIZONTAL_SPEED_TOLERANCE * 1.1, MAX_SPEED),
+            Some(ViolationKind::Speed)
+        );
+        assert_eq!(
+            kind(
+                Vec3::Y * (STEP_HEIGHT + MAX_UPWARD_SPEED * DELTA_SECS * 1.1),
+                MAX_SPEED
+            ),
+            Some(ViolationKind::Speed)
+        );
+        assert_eq!(
+            kind(Vec3::Z * (TELEPORT_DISTANCE + 0.1), MAX_SPEED),
+            Some(ViolationKind::Teleport)
+        );
+    }
+
+    #[test]
+    fn fast_surfaces_raise_the_speed_limit() {
+        let conveyor = BlockType {
+            id: BlockId(1),
+            name: "Conveyor".to_string(),
+            color: (0.0, 0.0, 0.0),
+            friction: 0.5,
+            restitution: 0.0,
+            density: 1.0,
+            rigid_body: RigidBody::Static,
+            surface: Surface {
+                conveyor: Vec3::X * 4.0,
+                ..default()
+            },
+        };
+        let registry = BlockRegistry::new([conveyor]).unwrap();
+        let conveyor_speed = max_speed(Some(&registry));
+        assert_eq!(conveyor_speed, MAX_SPEED + 4.0);
+        assert_eq!(max_speed(None), MAX_SPEED);
+
+        let carried = Vec3::X * (MAX_SPEED + 4.0) * DELTA_SECS * HORIZONTAL_SPEED_TOLERANCE * 0.99;
+        assert_eq!(kind(carried, conveyor_speed), None);
+        assert_eq!(kind(carried, MAX_SPEED), Some(ViolationKind::Speed));
+    }
+}
@@ -0,0 +1,181 @@
+use avian3d::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::{
+    block::BlockMarker,
+    character::CharacterMarker,
+    health::Dead,
+    inventory::{Inventory, ItemStack},
+    item::{ItemKind, ItemRegistry},
+    voxel::{BlockId, VoxelWorld},
+};
+use reclipsis_common::{
+    building::{self, BuildRejection, REACH_TOLERANCE},
+    protocol::*,
+};
+
+use crate::{
+    instance::{GameInstance, InInstance},
+    voxel::CellUpdated,
+};
+
+pub struct BuildingPlugin;
+
+impl Plugin for BuildingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            handle_build_requests
+                .before(crate::voxel::send_worlds)
+                .run_if(resource_exists::<ItemRegistry>),
+        );
+    }
+}
+
+#[derive(SystemParam)]
+struct Building<'w, 's> {
+    registry: Res<'w, ItemRegistry>,
+    spatial_query: SpatialQuery<'w, 's>,
+    characters: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static ControlledBy,
+            &'static Position,
+            &'static InInstance,
+            &'static CollisionLayers,
+            &'static mut Inventory,
+            Has<Dead>,
+        ),
+        With<CharacterMarker>,
+    >,
+    blocks: Query<'w, 's, (), With<BlockMarker>>,
+    instances: Query<'w, 's, (&'static GameInstance, &'static mut VoxelWorld)>,
+    cell_updates: EventWriter<'w, CellUpdated>,
+}
+
+impl Building<'_, '_> {
+    fn handle(&mut self, client: Entity, request: BuildRequest) {
+        let Some((character, instance)) = self
+            .characters
+            .iter()
+            .find(|(_, controlled_by, ..)| controlled_by.owner == client)
+            .map(|(character, _, _, in_instance, ..)| (character, in_instance.0))
+        else {
+            return;
+        };
+
+        let (result, cell) = match request {
+            BuildRequest::Place { cell, slot } => (self.place(character, cell, slot), cell),
+            BuildRequest::Remove { cell } => (self.remove(character, cell), cell),
+        };
+
+        // Edits and rejected predictions alike are fixed by sending the cell
+        self.cell_updates.write(CellUpdated {
+            world: instance,
+            cell,
+        });
+
+        if let Err(rejection) = result {
+            debug!("Rejected {request:?} from client {client:?}: {rejection:?}");
+            // The client already predicted it, replicating the unchanged
+            // inventory again rolls that back
+            if let Ok((.., mut inventory, _)) = self.characters.get_mut(character) {
+                inventory.set_changed();
+            }
+        }
+    }
+
+    /// Checks shared by placing and removing, returns the character's instance
+    fn check_access(&self, character: Entity, cell: IVec3) -> Result<Entity, BuildRejection> {
+        let Ok((_, _, position, in_instance, _, _, dead)) = self.characters.get(character) else {
+            return Err(BuildRejection::NoCharacter);
+        };
+
+        let allows_building = self
+            .instances
+            .get(in_instance.0)
+            .is_ok_and(|(instance, _)| instance.allows_building());
+        building::check_access(dead, allows_building, position.0, cell, REACH_TOLERANCE)?;
+
+        Ok(in_instance.0)
+    }
+
+    fn place(&mut self, character: Entity, cell: IVec3, slot: usize) -> Result<(), BuildRejection> {
+        let instance = self.check_access(character, cell)?;
+        let Ok((.., collision_layers, inventory, _)) = self.characters.get(character) else {
+            return Err(BuildRejection::NoCharacter);
+        };
+
+        let Some(ItemKind::Block { block }) = inventory
+            .get(slot)
+            .and_then(|stack| self.registry.get(stack.id))
+            .map(|item| item.kind.clone())
+        else {
+            return Err(BuildRejection::NotPlaceable);
+        };
+
+        let occupied = self.instances.get(instance).is_ok_and(|(_, world)| {
+            building::is_occupied(
+                world,
+                &self.spatial_query,
+                &SpatialQueryFilter::from_mask(collision_layers.memberships),
+                cell,
+                |entity| self.characters.contains(entity) || self.blocks.contains(entity),
+            )
+        });
+        if occupied {
+            return Err(BuildRejection::Occupied);
+        }
+
+        let Ok((.., mut inventory, _)) = self.characters.get_mut(character) else {
+            return Err(BuildRejection::NoCharacter);
+        };
+        inventory
+            .remove(slot, 1)
+            .map_err(BuildRejection::Inventory)?;
+
+        if let Ok((_, mut world)) = self.instances.get_mut(instance) {
+            world.set(cell, block);
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, character: Entity, cell: IVec3) -> Result<(), BuildRejection> {
+        let instance = self.check_access(character, cell)?;
+        let block = self
+            .instances
+            .get(instance)
+            .map_or(BlockId::AIR, |(_, world)| world.get(cell));
+        let item = self
+            .registry
+            .block_item(block)
+            .ok_or(BuildRejection::NoBlock)?;
+
+        let Ok((.., mut inventory, _)) = self.characters.get_mut(character) else {
+            return Err(BuildRejection::NoCharacter);
+        };
+        inventory
+            .add(&self.registry, ItemStack::new(item, 1))
+            .map_err(BuildRejection::Inventory)?;
+
+        if let Ok((_, mut world)) = self.instances.get_mut(instance) {
+            world.set(cell, BlockId::AIR);
+        }
+
+        Ok(())
+    }
+}
+
+fn handle_build_requests(
+    mut clients: Query<(Entity, &mut MessageReceiver<BuildRequest>), With<ClientOf>>,
+    mut building: Building,
+) {
+    for (client, mut receiver) in &mut clients {
+        for request in receiver.receive() {
+            building.handle(client, request);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_common::protocol::*;
+
+use crate::{
+    access::Admitted,
+    instance::{Instances, MoveToInstance},
+};
+
+const SERVER_SENDER: &str = "Server";
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, receive_chat_messages);
+    }
+}
+
+/// How players are called in chat and kill messages
+pub fn player_name(remote_id: &RemoteId) -> String {
+    format!("Player {}", remote_id.0.to_bits())
+}
+
+fn receive_chat_messages(
+    mut clients: Query<
+        (
+            Entity,
+            &RemoteId,
+            &mut MessageReceiver<ChatMessage>,
+            &mut MessageSender<ChatBroadcast>,
+        ),
+        (With<ClientOf>, With<Admitted>),
+    >,
+    instances: Instances,
+    mut move_events: EventWriter<MoveToInstance>,
+) {
+    let mut broadcasts = Vec::new();
+
+    for (client, remote_id, mut receiver, mut sender) in &mut clients {
+        for message in receiver.receive() {
+            let text = message.text.trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let mut reply = |text: String| {
+                sender.send::<ReliableChannel>(ChatBroadcast {
+                    sender: SERVER_SENDER.to_string(),
+                    text,
+                });
+            };
+
+            let args: Vec<&str> = text.split_whitespace().collect();
+            match args.as_slice() {
+                ["/instances"] => {
+                    for line in instances.describe() {
+                        reply(line);
+                    }
+                }
+                ["/join", name] => match instances.find(name) {
+                    Some(instance) => {
+                        move_events.write(MoveToInstance { client, instance });
+                    }
+                    None => reply(format!("No instance named {name:?}")),
+                },
+                [command, ..] if command.starts_with('/') => {
+                    reply(format!(
+                        "Unknown command {command:?}, try /instances or /join <instance>"
+                    ));
+                }
+                _ => broadcasts.push(ChatBroadcast {
+                    sender: player_name(remote_id),
+                    text: text.to_string(),
+                }),
+            }
+        }
+    }
+
+    for broadcast in broadcasts {
+        info!("[chat] {}: {}", broadcast.sender, broadcast.text);
+        for (_, _, _, mut sender) in &mut clients {
+            sender.send::<ReliableChannel>(broadcast.clone());
+        }
+    }
+}
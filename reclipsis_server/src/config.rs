@@ -0,0 +1,144 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use bevy::prelude::*;
+use reclipsis_master::DEFAULT_MASTER_ADDR;
+
+use crate::respawn::SpawnPolicy;
+
+#[derive(Resource, Clone, Debug)]
+pub struct ServerConfig {
+    pub name: String,
+    pub address: SocketAddr,
+    pub max_players: u32,
+    pub map: String,
+    // None disables the heartbeat
+    pub master_addr: Option<SocketAddr>,
+    // Whitelist and ban lists, see `access::AccessLists`
+    pub access_list_path: PathBuf,
+    // None only logs anti-cheat violations
+    pub kick_after_violations: Option<u32>,
+    pub spawn_policy: SpawnPolicy,
+    // Distance from a client's character within which entities are replicated to it
+    pub interest_radius: f32,
+    pub interest_hysteresis: f32,
+    // Bytes per second sent to each client, what fits is picked by `priority`.
+    // None sends everything
+    pub bandwidth_cap: Option<u32>,
+    // Time dead characters wait before respawning
+    pub respawn_delay_secs: f32,
+    // How far back melee hits are checked against past positions, see `melee`
+    pub max_rewind_ticks: u16,
+    // Logs every melee swing with the rewound positions it was checked against
+    pub debug_hits: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            name: "Reclipsis Server".to_string(),
+            address: "127.0.0.1:8080".parse().unwrap(),
+            max_players: 16,
+            map: "Flatland".to_string(),
+            master_addr: Some(DEFAULT_MASTER_ADDR.parse().unwrap()),
+            access_list_path: PathBuf::from("access.ron"),
+            kick_after_violations: None,
+            spawn_policy: SpawnPolicy::default(),
+            interest_radius: 40.0,
+            interest_hysteresis: 5.0,
+            bandwidth_cap: Some(50_000),
+            respawn_delay_secs: 3.0,
+            // 200ms at 60Hz
+            max_rewind_ticks: 12,
+            debug_hits: false,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads `--name`, `--port`, `--max-players`, `--map`, `--master`, `--no-master`,
+    /// `--access-list`, `--kick-after-violations`, `--spawn-policy`, `--interest-radius`,
+    /// `--respawn-delay`, `--max-rewind-ticks`, `--debug-hits`, `--bandwidth-cap` and
+    /// `--no-bandwidth-cap`
+    pub fn from_args() -> Self {
+        let mut config = Self::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--no-master" => config.master_addr = None,
+                "--no-bandwidth-cap" => config.bandwidth_cap = None,
+                "--debug-hits" => config.debug_hits = true,
+                "--name"
+                | "--port"
+                | "--max-players"
+                | "--map"
+                | "--master"
+                | "--access-list"
+                | "--kick-after-violations"
+                | "--spawn-policy"
+                | "--interest-radius"
+                | "--respawn-delay"
+                | "--max-rewind-ticks"
+                | "--bandwidth-cap" => {
+                    let Some(value) = args.next() else {
+                        warn!("Missing value for {arg}");
+                        continue;
+                    };
+                    config.apply(&arg, &value);
+                }
+                _ => warn!("Ignoring unknown argument {arg}"),
+            }
+        }
+
+        config
+    }
+
+    fn apply(&mut self, arg: &str, value: &str) {
+        match arg {
+            "--name" => self.name = value.to_string(),
+            "--map" => self.map = value.to_string(),
+            "--access-list" => self.access_list_path = PathBuf::from(value),
+            "--port" => match value.parse() {
+                Ok(port) => self.address.set_port(port),
+                Err(_) => warn!("Invalid port {value}"),
+            },
+            "--max-players" => match value.parse() {
+                Ok(max_players) => self.max_players = max_players,
+                Err(_) => warn!("Invalid max player count {value}"),
+            },
+            "--kick-after-violations" => match value.parse() {
+                Ok(count) => self.kick_after_violations = Some(count),
+                Err(_) => warn!("Invalid violation count {value}"),
+            },
+            "--spawn-policy" => match value.parse() {
+                Ok(policy) => self.spawn_policy = policy,
+                Err(_) => warn!(
+                    "Invalid spawn policy {value}, expected random, round-robin or least-crowded"
+                ),
+            },
+            "--interest-radius" => match value.parse() {
+                Ok(radius) => self.interest_radius = radius,
+                Err(_) => warn!("Invalid interest radius {value}"),
+            },
+            "--respawn-delay" => match value.parse() {
+                Ok(secs) => self.respawn_delay_secs = secs,
+                Err(_) => warn!("Invalid respawn delay {value}"),
+            },
+            "--max-rewind-ticks" => match value.parse() {
+                Ok(ticks) => self.max_rewind_ticks = ticks,
+                Err(_) => warn!("Invalid rewind tick count {value}"),
+            },
+            "--bandwidth-cap" => match value.parse() {
+                Ok(bytes_per_second) if bytes_per_second > 0 => {
+                    self.bandwidth_cap = Some(bytes_per_second)
+                }
+                _ => warn!("Invalid bandwidth cap {value}, expected bytes per second"),
+            },
+            "--master" => match value.parse() {
+                Ok(addr) => self.master_addr = Some(addr),
+                Err(_) => warn!("Invalid master server address {value}"),
+            },
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::{
+    character::CharacterMarker,
+    health::{Dead, Health},
+    inventory::ItemId,
+    item::ItemRegistry,
+};
+
+use crate::{chat::player_name, config::ServerConfig, respawn::Respawning};
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>().add_systems(
+            FixedUpdate,
+            apply_damage.after(crate::handle_character_actions),
+        );
+    }
+}
+
+/// What dealt damage, for attributing deaths
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DamageSource {
+    /// Another character's attack with an item
+    Item { attacker: Entity, item: ItemId },
+    /// Falling below the kill plane
+    Fall,
+}
+
+/// Damage to a character, only ever written on the server
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: DamageSource,
+}
+
+pub fn apply_damage(
+    mut commands: Commands,
+    config: Res<ServerConfig>,
+    // Only names the item in kill messages, falls still hurt while it loads
+    registry: Option<Res<ItemRegistry>>,
+    mut damage_events: EventReader<Damage>,
+    mut characters: Query<(&mut Health, Option<&ControlledBy>), With<CharacterMarker>>,
+    clients: Query<&RemoteId, With<ClientOf>>,
+) {
+    let name = |controlled_by: Option<&ControlledBy>| {
+        controlled_by
+            .and_then(|controlled_by| clients.get(controlled_by.owner).ok())
+            .map_or_else(|| "Someone".to_string(), player_name)
+    };
+
+    for damage in damage_events.read() {
+        let cause = match damage.source {
+            DamageSource::Item { attacker, item } => {
+                let attacker = characters
+                    .get(attacker)
+                    .map_or_else(|_| "Someone".to_string(), |(_, owner)| name(owner));
+                match registry.as_ref().and_then(|registry| registry.get(item)) {
+                    Some(item) => format!("{attacker} with {}", item.name),
+                    None => attacker,
+                }
+            }
+            DamageSource::Fall => "the fall".to_string(),
+        };
+
+        let Ok((mut health, controlled_by)) = characters.get_mut(damage.target) else {
+            continue;
+        };
+        // Dead characters stay dead until they respawn
+        if health.is_depleted() {
+            continue;
+        }
+
+        let taken = health.damage(damage.amount);
+        debug!(
+            "{} took {taken} damage from {cause}, {} left",
+            name(controlled_by),
+            health.current
+        );
+
+        if health.is_depleted() {
+            info!("{} was killed by {cause}", name(controlled_by));
+            commands.entity(damage.target).insert((
+                Dead {
+                    cause,
+                    respawn_delay_secs: config.respawn_delay_secs,
+                },
+                Respawning::new(config.respawn_delay_secs),
+            ));
+        }
+    }
+}
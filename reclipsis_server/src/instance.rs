@@ -0,0 +1,346 @@
+use avian3d::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::{voxel::BlockId, *};
+
+use crate::{
+    admin::AdminCommand, anticheat::InputValidation, interest::InterestManaged,
+    respawn::SpawnSelector,
+};
+
+pub const LOBBY: &str = "lobby";
+
+// Ids from assets/blocks
+const STONE: BlockId = BlockId(1);
+const ICE: BlockId = BlockId(2);
+const RUBBER: BlockId = BlockId(3);
+const HEAVY_CRATE: BlockId = BlockId(4);
+const STATIC_WALL: BlockId = BlockId(5);
+const MUD: BlockId = BlockId(6);
+const CONVEYOR: BlockId = BlockId(7);
+
+// Each instance gets its own collision layer, avian supports up to 32
+const INSTANCES: [(&str, &[(Vec3, BlockId)]); 3] = [
+    (LOBBY, &[(Vec3::new(1.0, 1.0, 0.0), STONE)]),
+    (
+        "match-1",
+        &[
+            (Vec3::new(5.0, 1.0, 5.0), ICE),
+            (Vec3::new(-5.0, 1.0, 5.0), RUBBER),
+            (Vec3::new(0.0, 1.0, -6.0), MUD),
+            (Vec3::new(8.0, 1.0, 0.0), CONVEYOR),
+        ],
+    ),
+    (
+        "match-2",
+        &[
+            (Vec3::new(5.0, 1.0, -5.0), HEAVY_CRATE),
+            (Vec3::new(-5.0, 1.0, -5.0), STONE),
+            (Vec3::new(0.0, 1.0, 8.0), STATIC_WALL),
+        ],
+    ),
+];
+
+const SPAWN_POINTS: [Vec3; 5] = [
+    Vec3::new(0.0, 2.0, 0.0),
+    Vec3::new(20.0, 2.0, 20.0),
+    Vec3::new(-20.0, 2.0, 20.0),
+    Vec3::new(20.0, 2.0, -20.0),
+    Vec3::new(-20.0, 2.0, -20.0),
+];
+
+pub struct InstancePlugin;
+
+impl Plugin for InstancePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MoveToInstance>()
+            .add_systems(Startup, spawn_instances)
+            .add_systems(Update, (handle_admin_commands, move_to_instance).chain())
+            .add_systems(
+                Update,
+                build_block_physics.run_if(resource_exists::<block::BlockRegistry>),
+            );
+    }
+}
+
+/// Independent world hosted by the server, replicated through its room
+#[derive(Component, Debug)]
+pub struct GameInstance {
+    pub name: String,
+    layer: u32,
+}
+
+impl GameInstance {
+    /// Everyone passes through the lobby, so it can't be built in
+    pub fn allows_building(&self) -> bool {
+        self.name != LOBBY
+    }
+
+    pub fn collision_layers(&self) -> CollisionLayers {
+        let mask = LayerMask(1 << self.layer);
+        CollisionLayers::new(mask, mask)
+    }
+}
+
+/// The instance an entity lives in
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InInstance(pub Entity);
+
+/// Moves the character of `client` into `instance`
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MoveToInstance {
+    pub client: Entity,
+    pub instance: Entity,
+}
+
+#[derive(SystemParam)]
+pub struct Instances<'w, 's> {
+    instances: Query<'w, 's, (Entity, &'static GameInstance)>,
+    characters: Query<'w, 's, &'static InInstance, With<character::CharacterMarker>>,
+}
+
+impl Instances<'_, '_> {
+    pub fn find(&self, name: &str) -> Option<Entity> {
+        self.instances
+            .iter()
+            .find(|(_, instance)| instance.name == name)
+            .map(|(entity, _)| entity)
+    }
+
+    pub fn lobby(&self) -> (Entity, &GameInstance) {
+        self.instances
+            .iter()
+            .find(|(_, instance)| instance.name == LOBBY)
+            .expect("the lobby is spawned on startup")
+    }
+
+    /// Lines like `lobby (2 players)`
+    pub fn describe(&self) -> Vec<String> {
+        self.instances
+            .iter()
+            .map(|(entity, instance)| {
+                let players = self
+                    .characters
+                    .iter()
+                    .filter(|in_instance| in_instance.0 == entity)
+                    .count();
+                format!("{} ({players} players)", instance.name)
+            })
+            .collect()
+    }
+}
+
+fn spawn_instances(mut commands: Commands) {
+    for (layer, (name, blocks)) in INSTANCES.into_iter().enumerate() {
+        let game_instance = GameInstance {
+            name: name.to_string(),
+            layer: layer as u32,
+        };
+        let collision_layers = game_instance.collision_layers();
+
+        let instance = commands
+            .spawn((
+                Name::new(format!("Instance {name}")),
+                game_instance,
+                voxel::VoxelWorld::default(),
+                Room::default(),
+            ))
+            .id();
+
+        let floor = commands
+            .spawn((
+                Name::new("Floor"),
+                floor::FloorPhysicsBundle::default(),
+                floor::FloorMarker,
+                Position::new(Vec3::ZERO),
+                Replicate::to_clients(NetworkTarget::All),
+                NetworkVisibility::default(),
+                InInstance(instance),
+                collision_layers,
+            ))
+            .id();
+
+        // Static level geometry is scoped by the room, dynamic entities by interest
+        commands.trigger(RoomEvent {
+            room: instance,
+            target: RoomTarget::AddEntity(floor),
+        });
+
+        // Their physics is added once the block registry is loaded
+        for (translation, kind) in blocks {
+            commands.spawn((
+                Name::new("Block"),
+                block::BlockMarker,
+                block::BlockKind(*kind),
+                Position::new(*translation),
+                Replicate::to_clients(NetworkTarget::All),
+                PredictionTarget::to_clients(NetworkTarget::All),
+                InterestManaged,
+                InInstance(instance),
+                collision_layers,
+            ));
+        }
+
+        for translation in SPAWN_POINTS {
+            let look_target = Vec3::new(0.0, translation.y, 0.0);
+            commands.spawn((
+                Name::new("Spawn Point"),
+                spawn::SpawnPoint,
+                Transform::from_translation(translation).looking_at(look_target, Vec3::Y),
+                InInstance(instance),
+            ));
+        }
+
+        info!("Spawned instance {name}");
+    }
+}
+
+fn build_block_physics(
+    mut commands: Commands,
+    registry: Res<block::BlockRegistry>,
+    blocks: Query<(Entity, &block::BlockKind), Without<RigidBody>>,
+) {
+    for (entity, kind) in &blocks {
+        match registry.get(kind.0) {
+            Some(block_type) => {
+                commands
+                    .entity(entity)
+                    .insert(block::BlockPhysicsBundle::new(block_type));
+            }
+            None => {
+                warn!("Despawning block of unknown kind {:?}", kind.0);
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+fn move_to_instance(
+    mut commands: Commands,
+    mut events: EventReader<MoveToInstance>,
+    instances: Query<&GameInstance>,
+    mut params: ParamSet<(
+        SpawnSelector,
+        Query<
+            (
+                Entity,
+                &ControlledBy,
+                &mut InInstance,
+                &mut CollisionLayers,
+                &mut Position,
+                &mut LinearVelocity,
+                &mut Transform,
+                Option<&mut InputValidation>,
+            ),
+            With<character::CharacterMarker>,
+        >,
+    )>,
+) {
+    for event in events.read() {
+        let Ok(game_instance) = instances.get(event.instance) else {
+            warn!("Can't move to {:?}, it isn't an instance", event.instance);
+            continue;
+        };
+
+        let Some(character) = params
+            .p1()
+            .iter()
+            .find(|(_, controlled_by, ..)| controlled_by.owner == event.client)
+            .map(|(entity, ..)| entity)
+        else {
+            warn!("Client {:?} has no character to move", event.client);
+            continue;
+        };
+
+        let spawn = params.p0().select(event.instance);
+
+        let mut characters = params.p1();
+        let Ok((
+            _,
+            _,
+            mut in_instance,
+            mut collision_layers,
+            mut position,
+            mut linear_velocity,
+            mut transform,
+            validation,
+        )) = characters.get_mut(character)
+        else {
+            continue;
+        };
+
+        let previous = in_instance.0;
+        if previous == event.instance {
+            continue;
+        }
+
+        in_instance.0 = event.instance;
+        *collision_layers = game_instance.collision_layers();
+        position.0 = spawn.translation;
+        linear_velocity.0 = Vec3::ZERO;
+        // Keep the transform in sync, or physics would copy the old one back
+        *transform = spawn;
+        if let Some(mut validation) = validation {
+            validation.reset_position();
+        }
+
+        commands.trigger(RoomEvent {
+            room: previous,
+            target: RoomTarget::RemoveSender(event.client),
+        });
+        commands.trigger(RoomEvent {
+            room: event.instance,
+            target: RoomTarget::AddSender(event.client),
+        });
+
+        info!(
+            "Moved character {character:?} of client {:?} to instance {}",
+            event.client, game_instance.name
+        );
+    }
+}
+
+fn handle_admin_commands(
+    mut admin_commands: EventReader<AdminCommand>,
+    mut move_events: EventWriter<MoveToInstance>,
+    instances: Instances,
+    clients: Query<(Entity, &RemoteId), With<ClientOf>>,
+) {
+    for command in admin_commands.read() {
+        match command {
+            AdminCommand::Instances => {
+                for line in instances.describe() {
+                    info!("{line}");
+                }
+            }
+            AdminCommand::MoveToInstance(client_id, name) => {
+                let Some(instance) = instances.find(name) else {
+                    warn!("No instance named {name:?}");
+                    continue;
+                };
+                let Some((client, _)) = clients
+                    .iter()
+                    .find(|(_, remote_id)| remote_id.0.to_bits() == *client_id)
+                else {
+                    warn!("No client with id {client_id}");
+                    continue;
+                };
+
+                move_events.write(MoveToInstance { client, instance });
+            }
+            // Handled by the console, the access and the metrics plugins
+            AdminCommand::Help
+            | AdminCommand::Players
+            | AdminCommand::Kick(_)
+            | AdminCommand::Ban(_)
+            | AdminCommand::Unban(_)
+            | AdminCommand::BanIp(_)
+            | AdminCommand::UnbanIp(_)
+            | AdminCommand::SetWhitelist(_)
+            | AdminCommand::WhitelistAdd(_)
+            | AdminCommand::WhitelistRemove(_)
+            | AdminCommand::MaxPlayers(_)
+            | AdminCommand::Bandwidth => {}
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::character::CharacterMarker;
+
+use crate::{config::ServerConfig, instance::InInstance};
+
+pub struct InterestPlugin;
+
+impl Plugin for InterestPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_visibility)
+            .add_observer(add_network_visibility);
+    }
+}
+
+/// Replicated only to clients whose character is within the interest radius
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct InterestManaged;
+
+fn add_network_visibility(trigger: Trigger<OnAdd, InterestManaged>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert(NetworkVisibility::default());
+}
+
+fn update_visibility(
+    config: Res<ServerConfig>,
+    clients: Query<Entity, (With<ClientOf>, With<Connected>)>,
+    characters: Query<(&Position, &InInstance, &ControlledBy), With<CharacterMarker>>,
+    mut managed: Query<
+        (
+            &Position,
+            &InInstance,
+            &mut NetworkVisibility,
+            Option<&ControlledBy>,
+        ),
+        With<InterestManaged>,
+    >,
+) {
+    let gain_radius = config.interest_radius;
+    // Entities only leave once they are a bit further out, so ones sitting on the
+    // edge don't keep spawning and despawning
+    let lose_radius = config.interest_radius + config.interest_hysteresis;
+
+    let viewers: HashMap<Entity, (Vec3, Entity)> = characters
+        .iter()
+        .map(|(position, in_instance, controlled_by)| {
+            (controlled_by.owner, (position.0, in_instance.0))
+        })
+        .collect();
+
+    for (position, in_instance, mut visibility, controlled_by) in &mut managed {
+        for client in &clients {
+            // Clients always see their own character
+            if controlled_by.is_some_and(|controlled_by| controlled_by.owner == client) {
+                if !visibility.is_visible(client) {
+                    visibility.gain_visibility(client);
+                }
+                continue;
+            }
+
+            // Entities in other instances are never visible
+            let distance = viewers
+                .get(&client)
+                .filter(|(_, instance)| *instance == in_instance.0)
+                .map(|(viewer, _)| viewer.distance(position.0));
+            let is_visible = visibility.is_visible(client);
+
+            match distance {
+                Some(distance) if !is_visible && distance <= gain_radius => {
+                    visibility.gain_visibility(client);
+                }
+                Some(distance) if is_visible && distance > lose_radius => {
+                    visibility.lose_visibility(client);
+                }
+                // Queued clients without a character see nothing either
+                None if is_visible => visibility.lose_visibility(client),
+                _ => {}
+            }
+        }
+    }
+}
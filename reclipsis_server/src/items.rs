@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::{
+    character::CharacterMarker,
+    health::Dead,
+    inventory::{Inventory, ItemStack},
+    item::{ItemKind, ItemRegistry},
+    world_item::{PickupDelay, WorldItem, WorldItemPhysicsBundle},
+};
+use reclipsis_common::{item_use::ItemUsed, pickup, protocol::*};
+
+use crate::{instance::InInstance, interest::InterestManaged};
+
+// Dropped items land in front of the character, out of its pickup radius
+const DROP_DISTANCE: f32 = 1.5;
+const DROP_HEIGHT: f32 = 0.5;
+const DROP_SPEED: f32 = 2.0;
+
+pub struct ItemsPlugin;
+
+impl Plugin for ItemsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ItemDropped>()
+            .add_systems(
+                Update,
+                (
+                    handle_inventory_requests.run_if(resource_exists::<ItemRegistry>),
+                    spawn_dropped_items,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                FixedUpdate,
+                (consume_used_items, pick_up_world_items)
+                    .after(crate::handle_character_actions)
+                    .run_if(resource_exists::<ItemRegistry>),
+            );
+    }
+}
+
+/// Items a character took out of its inventory to drop into the world
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ItemDropped {
+    pub character: Entity,
+    pub stack: ItemStack,
+}
+
+fn handle_inventory_requests(
+    registry: Res<ItemRegistry>,
+    mut clients: Query<(Entity, &mut MessageReceiver<InventoryRequest>), With<ClientOf>>,
+    mut characters: Query<(Entity, &ControlledBy, &mut Inventory), With<CharacterMarker>>,
+    mut dropped: EventWriter<ItemDropped>,
+) {
+    for (client, mut receiver) in &mut clients {
+        for request in receiver.receive() {
+            let Some((character, _, mut inventory)) = characters
+                .iter_mut()
+                .find(|(_, controlled_by, _)| controlled_by.owner == client)
+            else {
+                continue;
+            };
+
+            match request.apply(&mut inventory, &registry) {
+                Ok(Some(stack)) => {
+                    dropped.write(ItemDropped { character, stack });
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    debug!("Rejected {request:?} from client {client:?}: {err}");
+                    // The client already applied it, replicating the unchanged
+                    // inventory again rolls that back
+                    inventory.set_changed();
+                }
+            }
+        }
+    }
+}
+
+fn spawn_dropped_items(
+    mut commands: Commands,
+    mut dropped: EventReader<ItemDropped>,
+    characters: Query<(&Position, &Rotation, &InInstance, &CollisionLayers), With<CharacterMarker>>,
+) {
+    for event in dropped.read() {
+        let Ok((position, rotation, in_instance, collision_layers)) =
+            characters.get(event.character)
+        else {
+            warn!("Dropping {:?} without a character", event.stack);
+            continue;
+        };
+
+        let forward = rotation.0 * Vec3::NEG_Z;
+        commands.spawn((
+            Name::new("World Item"),
+            WorldItem { stack: event.stack },
+            PickupDelay(pickup::DROP_PICKUP_DELAY_TICKS),
+            WorldItemPhysicsBundle::default(),
+            Position(position.0 + forward * DROP_DISTANCE + Vec3::Y * DROP_HEIGHT),
+            Rotation::default(),
+            LinearVelocity(forward * DROP_SPEED),
+            Replicate::to_clients(NetworkTarget::All),
+            PredictionTarget::to_clients(NetworkTarget::All),
+            InterestManaged,
+            *in_instance,
+            *collision_layers,
+        ));
+    }
+}
+
+// The simulation only applies their effect, see `item_use::ConsumableBehavior`
+fn consume_used_items(
+    registry: Res<ItemRegistry>,
+    mut item_used: EventReader<ItemUsed>,
+    mut characters: Query<&mut Inventory, With<CharacterMarker>>,
+) {
+    for used in item_used.read() {
+        let consumable = registry
+            .get(used.item)
+            .is_some_and(|item| matches!(item.kind, ItemKind::Consumable { .. }));
+        if !consumable {
+            continue;
+        }
+        let Ok(mut inventory) = characters.get_mut(used.character) else {
+            continue;
+        };
+
+        if let Err(err) = inventory.remove(used.slot, 1) {
+            warn!(
+                "Can't consume {:?} from slot {}: {err}",
+                used.item, used.slot
+            );
+        }
+    }
+}
+
+// Clients predict the same pickups, see `pickup::pick_up_items`
+fn pick_up_world_items(
+    mut commands: Commands,
+    registry: Res<ItemRegistry>,
+    mut characters: Query<
+        (
+            &ActionState<CharacterAction>,
+            &Position,
+            &InInstance,
+            Has<Dead>,
+            &mut Inventory,
+        ),
+        With<CharacterMarker>,
+    >,
+    items: Query<(
+        Entity,
+        &WorldItem,
+        &Position,
+        &InInstance,
+        Option<&PickupDelay>,
+    )>,
+) {
+    // Despawns are deferred, so items taken earlier this tick are tracked here to
+    // keep two characters from picking up the same one
+    let mut taken = HashSet::new();
+
+    for (action_state, position, in_instance, dead, mut inventory) in &mut characters {
+        let nearby = items
+            .iter()
+            .filter(|(item, _, _, item_instance, _)| {
+                *item_instance == in_instance && !taken.contains(item)
+            })
+            .map(|(item, world_item, item_position, _, delay)| {
+                (item, item_position.0, world_item.stack, delay)
+            });
+
+        let picked_up = pickup::pick_up_items(
+            &registry,
+            action_state,
+            position.0,
+            dead,
+            &mut inventory,
+            nearby,
+        );
+        for item in picked_up {
+            taken.insert(item);
+            commands.entity(item).despawn();
+        }
+    }
+}
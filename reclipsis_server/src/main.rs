@@ -3,13 +3,39 @@ use bevy::{log::LogPlugin, prelude::*, render::mesh::MeshPlugin, scene::ScenePlu
 use leafwing_input_manager::prelude::*;
 use lightyear::prelude::{server::*, *};
 
+use governor::Quota;
 use reclipsis_common::{
-    CharacterQuery, FIXED_TIMESTEP_HZ, apply_character_action, protocol::CharacterAction,
+    CharacterQuery, FIXED_TIMESTEP_HZ, apply_character_action, character_controller,
+    item_use::{ItemBehaviors, ItemUsed, use_equipped_item},
+    protocol::CharacterAction,
+    rotate_value,
 };
-use std::time::Duration;
+use std::{num::NonZeroU32, time::Duration};
 
 use reclipsis_assets::*;
 
+mod access;
+mod admin;
+mod anticheat;
+mod building;
+mod chat;
+mod config;
+mod health;
+mod instance;
+mod interest;
+mod items;
+mod master;
+mod melee;
+mod metrics;
+mod priority;
+mod projectiles;
+mod registry;
+mod respawn;
+mod voxel;
+
+// The assets directory is shared with the client at the workspace root
+const ASSETS_PATH: &str = "../assets";
+
 pub const SEND_INTERVAL: Duration = Duration::from_millis(100);
 
 fn main() {
@@ -17,76 +43,138 @@ fn main() {
         .add_plugins(
             MinimalPlugins
                 .build()
-                .add(AssetPlugin::default())
+                .add(AssetPlugin {
+                    file_path: ASSETS_PATH.to_string(),
+                    ..default()
+                })
                 .add(MeshPlugin)
                 .add(ScenePlugin)
                 .add(LogPlugin::default()),
         )
+        .insert_resource(config::ServerConfig::from_args())
         .add_plugins(reclipsis_common::SharedPlugin)
         .add_plugins(server::ServerPlugins {
             tick_duration: Duration::from_secs_f64(1.0 / FIXED_TIMESTEP_HZ),
         })
-        .add_systems(Startup, setup)
-        .add_systems(FixedUpdate, handle_character_actions)
+        .add_plugins((
+            admin::AdminPlugin,
+            access::AccessPlugin,
+            anticheat::AntiCheatPlugin,
+            chat::ChatPlugin,
+            instance::InstancePlugin,
+            interest::InterestPlugin,
+            master::MasterPlugin,
+            metrics::MetricsPlugin,
+            priority::PriorityPlugin,
+            registry::RegistryPlugin,
+        ))
+        // Gameplay
+        .add_plugins((
+            building::BuildingPlugin,
+            health::HealthPlugin,
+            items::ItemsPlugin,
+            melee::MeleePlugin,
+            projectiles::ProjectilesPlugin,
+            respawn::RespawnPlugin,
+            voxel::VoxelPlugin,
+        ))
+        .add_systems(
+            Update,
+            start_server.run_if(
+                resource_exists::<item::ItemRegistry>
+                    .and(resource_exists::<block::BlockRegistry>)
+                    .and(not(any_with_component::<NetcodeServer>)),
+            ),
+        )
+        .add_systems(
+            FixedUpdate,
+            handle_character_actions.run_if(resource_exists::<item::ItemRegistry>),
+        )
         .add_observer(handle_new_client)
         .add_observer(handle_connected)
         .run();
 }
 
-fn setup(mut commands: Commands) {
+// Clients are only accepted once the definitions they are checked against are loaded
+fn start_server(mut commands: Commands, config: Res<config::ServerConfig>) {
+    info!("Starting server {:?} on {}", config.name, config.address);
+
     let server = commands
         .spawn((
             NetcodeServer::new(NetcodeConfig::default()),
-            LocalAddr("127.0.0.1:8080".parse().unwrap()),
+            LocalAddr(config.address),
             ServerUdpIo::default(),
         ))
         .id();
     commands.trigger_targets(Start, server);
+}
+
+fn handle_new_client(
+    trigger: Trigger<OnAdd, LinkOf>,
+    mut commands: Commands,
+    config: Res<config::ServerConfig>,
+) {
+    let bandwidth_cap = config.bandwidth_cap.and_then(NonZeroU32::new);
+    let sender = ReplicationSender::new(
+        SEND_INTERVAL,
+        SendUpdatesMode::SinceLastAck,
+        bandwidth_cap.is_some(),
+    );
 
-    commands.spawn((
-        Name::new("Floor"),
-        floor::FloorPhysicsBundle::default(),
-        floor::FloorMarker,
-        Position::new(Vec3::ZERO),
-        Replicate::to_clients(NetworkTarget::All),
-    ));
-
-    commands.spawn((
-        Name::new("Block"),
-        block::BlockPhysicsBundle::default(),
-        block::BlockMarker,
-        Position::new(Vec3::new(1.0, 1.0, 0.0)),
-        Replicate::to_clients(NetworkTarget::All),
-        PredictionTarget::to_clients(NetworkTarget::All),
-    ));
+    // Entities are sent by accumulated priority within the client's budget, see `priority`
+    match bandwidth_cap {
+        Some(bytes_per_second) => commands.entity(trigger.target()).insert((
+            sender,
+            Transport::new(PriorityConfig::new(Quota::per_second(bytes_per_second))),
+        )),
+        None => commands.entity(trigger.target()).insert(sender),
+    };
 }
 
-fn handle_new_client(trigger: Trigger<OnAdd, LinkOf>, mut commands: Commands) {
-    commands
-        .entity(trigger.target())
-        .insert(ReplicationSender::new(
-            SEND_INTERVAL,
-            SendUpdatesMode::SinceLastAck,
-            false,
-        ));
+// Ids from assets/items
+const STARTING_ITEMS: [(u32, u32); 4] = [(1, 1), (2, 1), (3, 32), (4, 2)];
+
+fn starting_inventory(item_registry: &item::ItemRegistry) -> inventory::Inventory {
+    let mut inventory = inventory::Inventory::default();
+
+    for (id, count) in STARTING_ITEMS {
+        let stack = inventory::ItemStack::new(inventory::ItemId(id), count);
+        if let Err(err) = inventory.add(item_registry, stack) {
+            warn!("Can't add starting item {id}: {err}");
+        }
+    }
+
+    inventory
 }
 
 fn handle_connected(
-    trigger: Trigger<OnAdd, Connected>,
+    trigger: Trigger<OnAdd, access::Admitted>,
     query: Query<&RemoteId, With<ClientOf>>,
+    instances: instance::Instances,
+    mut spawn_selector: respawn::SpawnSelector,
+    item_registry: Res<item::ItemRegistry>,
     mut commands: Commands,
 ) {
     let Ok(client_id) = query.get(trigger.target()) else {
         return;
     };
     let client_id = client_id.0;
-    info!("Client connected with client-id {client_id:?}. Spawning character entity.");
+    info!("Client admitted with client-id {client_id:?}. Spawning character entity.");
+
+    // Everyone starts out in the lobby
+    let (lobby, lobby_instance) = instances.lobby();
+    let spawn = spawn_selector.select(lobby);
+    // Faces the same way as the client's first inputs, which it seeds from the
+    // replicated rotation
+    let mut action_state = ActionState::<CharacterAction>::default();
+    action_state.set_value(&CharacterAction::Rotate, rotate_value(spawn.rotation));
 
     let character = commands
         .spawn((
             Name::new("Character"),
-            ActionState::<CharacterAction>::default(),
-            Position(Vec3::new(0.0, 5.0, 0.0)),
+            action_state,
+            Position(spawn.translation),
+            Rotation(spawn.rotation),
             Replicate::to_clients(NetworkTarget::All),
             PredictionTarget::to_clients(NetworkTarget::All),
             ControlledBy {
@@ -95,19 +183,41 @@ fn handle_connected(
             },
             character::CharacterPhysicsBundle::default(),
             character::CharacterMarker,
-            inventory::Inventory::default(),
+            character::JumpState::default(),
+            starting_inventory(&item_registry),
+            inventory::EquippedSlot::default(),
+            inventory::ItemCooldowns::default(),
+            reclipsis_assets::health::Health::default(),
+            interest::InterestManaged,
+            instance::InInstance(lobby),
+            lobby_instance.collision_layers(),
         ))
         .id();
 
+    commands.trigger(RoomEvent {
+        room: lobby,
+        target: RoomTarget::AddSender(trigger.target()),
+    });
+
     info!("Created entity {character:?} for client {client_id:?}");
 }
 
 fn handle_character_actions(
-    time: Res<Time>,
-    spatial_query: SpatialQuery,
+    environment: character_controller::CharacterEnvironment,
+    item_registry: Res<item::ItemRegistry>,
+    item_behaviors: Res<ItemBehaviors>,
     mut query: Query<(&ActionState<CharacterAction>, CharacterQuery)>,
+    mut item_used: EventWriter<ItemUsed>,
 ) {
     for (action_state, mut character) in &mut query {
-        apply_character_action(&time, &spatial_query, action_state, &mut character);
+        apply_character_action(&environment, action_state, &mut character);
+        if let Some(used) = use_equipped_item(
+            &item_registry,
+            &item_behaviors,
+            action_state,
+            &mut character,
+        ) {
+            item_used.write(used);
+        }
     }
 }
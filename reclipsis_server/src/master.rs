@@ -0,0 +1,108 @@
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+};
+
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_master::*;
+
+use crate::{access::Admitted, config::ServerConfig};
+
+pub struct MasterPlugin;
+
+impl Plugin for MasterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_heartbeat).add_systems(
+            Update,
+            (send_heartbeat, answer_pings).run_if(resource_exists::<MasterConnection>),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct MasterConnection {
+    socket: UdpSocket,
+    master_addr: SocketAddr,
+    timer: Timer,
+}
+
+fn setup_heartbeat(mut commands: Commands, config: Res<ServerConfig>) {
+    let Some(master_addr) = config.master_addr else {
+        info!("No master server configured, not sending heartbeats");
+        return;
+    };
+
+    let socket = match UdpSocket::bind((config.address.ip(), 0)) {
+        Ok(socket) => socket,
+        Err(err) => {
+            error!("Failed to bind heartbeat socket: {err}");
+            return;
+        }
+    };
+
+    if let Err(err) = socket.set_nonblocking(true) {
+        error!("Failed to set up heartbeat socket: {err}");
+        return;
+    }
+
+    info!("Sending heartbeats to master server at {master_addr}");
+
+    let mut timer = Timer::new(HEARTBEAT_INTERVAL, TimerMode::Repeating);
+    // Register right away instead of after the first interval
+    timer.tick(HEARTBEAT_INTERVAL);
+
+    commands.insert_resource(MasterConnection {
+        socket,
+        master_addr,
+        timer,
+    });
+}
+
+fn send_heartbeat(
+    time: Res<Time>,
+    config: Res<ServerConfig>,
+    mut master: ResMut<MasterConnection>,
+    players: Query<(), (With<Admitted>, With<Connected>)>,
+) {
+    if !master.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let heartbeat = MasterPacket::Heartbeat(ServerInfo {
+        name: config.name.clone(),
+        address: config.address,
+        players: players.iter().count() as u32,
+        max_players: config.max_players,
+        map: config.map.clone(),
+    });
+
+    if let Err(err) = master
+        .socket
+        .send_to(&heartbeat.to_bytes(), master.master_addr)
+    {
+        warn!("Failed to send heartbeat: {err}");
+    }
+}
+
+fn answer_pings(master: Res<MasterConnection>) {
+    let mut buf = [0; MAX_PACKET_SIZE];
+
+    loop {
+        // Clients ping the socket the master saw our heartbeats come from
+        let (len, src) = match master.socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+            Err(err) => {
+                warn!("Failed to receive on heartbeat socket: {err}");
+                return;
+            }
+        };
+
+        if let Some(MasterPacket::Ping(nonce)) = MasterPacket::from_bytes(&buf[..len]) {
+            let _ = master
+                .socket
+                .send_to(&MasterPacket::Pong(nonce).to_bytes(), src);
+        }
+    }
+}
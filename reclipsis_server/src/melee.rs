@@ -0,0 +1,237 @@
+use std::{collections::VecDeque, time::Duration};
+
+use avian3d::{collision::collider::contact_query, prelude::*};
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::{
+    character::{self, CharacterMarker},
+    health::Dead,
+    item::{ItemKind, ItemRegistry},
+};
+use reclipsis_common::{
+    FIXED_TIMESTEP_HZ,
+    item_use::{ItemUsed, UseAction},
+};
+
+use crate::{
+    config::ServerConfig,
+    health::{Damage, DamageSource},
+    instance::InInstance,
+};
+
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            resolve_melee_attacks
+                .after(crate::handle_character_actions)
+                .before(crate::health::apply_damage)
+                .run_if(resource_exists::<ItemRegistry>),
+        )
+        // After physics moved everything for the tick
+        .add_systems(FixedLast, record_position_history)
+        .add_observer(add_position_history);
+    }
+}
+
+/// Where a character was on recent ticks, newest last
+#[derive(Component, Default, Debug)]
+pub struct PositionHistory(VecDeque<(Tick, Vec3)>);
+
+impl PositionHistory {
+    /// Adds the position on `tick`, keeping enough to rewind `max_rewind_ticks`
+    pub fn record(&mut self, tick: Tick, position: Vec3, max_rewind_ticks: u16) {
+        self.0.push_back((tick, position));
+        while self.0.len() > max_rewind_ticks as usize + 1 {
+            self.0.pop_front();
+        }
+    }
+
+    /// Position on `tick`, or the closest one recorded
+    pub fn at(&self, tick: Tick) -> Option<Vec3> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(recorded, _)| tick - *recorded >= 0)
+            .or(self.0.front())
+            .map(|(_, position)| *position)
+    }
+}
+
+fn add_position_history(trigger: Trigger<OnAdd, CharacterMarker>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert(PositionHistory::default());
+}
+
+fn record_position_history(
+    config: Res<ServerConfig>,
+    timeline: Single<&LocalTimeline, With<Server>>,
+    mut characters: Query<(&Position, &mut PositionHistory)>,
+) {
+    let tick = timeline.tick();
+
+    for (position, mut history) in &mut characters {
+        history.record(tick, position.0, config.max_rewind_ticks);
+    }
+}
+
+/// Ticks between what the client of `attacker` showed and the server's present
+fn rewind_ticks(delay: Duration, max_rewind_ticks: u16) -> u16 {
+    let ticks = delay.as_secs_f64() * FIXED_TIMESTEP_HZ;
+
+    (ticks.round() as u16).min(max_rewind_ticks)
+}
+
+// Characters are checked where the attacker's client showed them, so hits that
+// looked right to the attacker land even though the target moved on since
+fn resolve_melee_attacks(
+    config: Res<ServerConfig>,
+    registry: Res<ItemRegistry>,
+    timeline: Single<&LocalTimeline, With<Server>>,
+    mut item_used: EventReader<ItemUsed>,
+    characters: Query<
+        (
+            Entity,
+            &Position,
+            &Rotation,
+            &PositionHistory,
+            &InInstance,
+            &ControlledBy,
+            Has<Dead>,
+        ),
+        With<CharacterMarker>,
+    >,
+    clients: Query<&InterpolationDelay, With<ClientOf>>,
+    mut damage: EventWriter<Damage>,
+) {
+    let now = timeline.tick();
+    let target_collider = Collider::capsule(
+        character::CHARACTER_CAPSULE_RADIUS,
+        character::CHARACTER_CAPSULE_HEIGHT,
+    );
+
+    for used in item_used.read() {
+        if used.action != UseAction::Primary {
+            continue;
+        }
+        let Some(ItemKind::Melee {
+            damage: amount,
+            range,
+            ..
+        }) = registry.get(used.item).map(|item| &item.kind)
+        else {
+            continue;
+        };
+        let Ok((_, position, rotation, _, in_instance, controlled_by, _)) =
+            characters.get(used.character)
+        else {
+            continue;
+        };
+
+        let delay = clients
+            .get(controlled_by.owner)
+            .map_or(Duration::ZERO, |delay| {
+                Duration::from_millis(delay.delay_ms as u64)
+            });
+        let rewind = rewind_ticks(delay, config.max_rewind_ticks);
+        let seen_tick = now - rewind as i16;
+
+        // Sphere reaching `range` in front of the attacker
+        let hit_collider = Collider::sphere(range / 2.0);
+        let hit_position = position.0 + rotation.0 * Vec3::NEG_Z * (range / 2.0);
+
+        for (target, _, _, history, target_instance, _, is_dead) in &characters {
+            if target == used.character || target_instance != in_instance || is_dead {
+                continue;
+            }
+            let Some(target_position) = history.at(seen_tick) else {
+                continue;
+            };
+
+            let hit = contact_query::intersection_test(
+                &hit_collider,
+                hit_position,
+                Quat::IDENTITY,
+                &target_collider,
+                target_position,
+                Quat::IDENTITY,
+            )
+            .unwrap_or(false);
+
+            if config.debug_hits {
+                info!(
+                    "Swing of {:?} at {hit_position} rewound {rewind} ticks, target {target:?} at {target_position}: {}",
+                    used.character,
+                    if hit { "hit" } else { "miss" }
+                );
+            }
+
+            if hit {
+                damage.write(Damage {
+                    target,
+                    amount: *amount,
+                    source: DamageSource::Item {
+                        attacker: used.character,
+                        item: used.item,
+                    },
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(ticks: impl IntoIterator<Item = u16>, max_rewind_ticks: u16) -> PositionHistory {
+        let mut history = PositionHistory::default();
+        for tick in ticks {
+            history.record(Tick(tick), Vec3::X * tick as f32, max_rewind_ticks);
+        }
+        history
+    }
+
+    #[test]
+    fn history_keeps_only_the_rewind_window() {
+        let history = history(0..100, 12);
+
+        assert_eq!(history.0.len(), 13);
+        assert_eq!(history.0.front().map(|(tick, _)| *tick), Some(Tick(87)));
+        assert_eq!(history.0.back().map(|(tick, _)| *tick), Some(Tick(99)));
+    }
+
+    #[test]
+    fn history_looks_up_recorded_ticks() {
+        let history = history(10..20, 12);
+
+        assert_eq!(history.at(Tick(15)), Some(Vec3::X * 15.0));
+        // Before the oldest entry the oldest position is the best guess
+        assert_eq!(history.at(Tick(3)), Some(Vec3::X * 10.0));
+        // Ticks not recorded yet use the newest
+        assert_eq!(history.at(Tick(25)), Some(Vec3::X * 19.0));
+        assert_eq!(PositionHistory::default().at(Tick(15)), None);
+    }
+
+    #[test]
+    fn history_lookup_survives_tick_wrap_around() {
+        let history = history((u16::MAX - 4..=u16::MAX).chain(0..5), 12);
+
+        assert_eq!(history.at(Tick(u16::MAX)), Some(Vec3::X * u16::MAX as f32));
+        assert_eq!(history.at(Tick(2)), Some(Vec3::X * 2.0));
+    }
+
+    #[test]
+    fn rewind_covers_the_interpolation_delay() {
+        let tick = Duration::from_secs_f64(1.0 / FIXED_TIMESTEP_HZ);
+
+        assert_eq!(rewind_ticks(Duration::ZERO, 12), 0);
+        assert_eq!(rewind_ticks(tick * 5, 12), 5);
+        assert_eq!(rewind_ticks(Duration::from_millis(100), 12), 6);
+        // Clients with huge delays don't get to rewind further
+        assert_eq!(rewind_ticks(Duration::from_secs(2), 12), 12);
+    }
+}
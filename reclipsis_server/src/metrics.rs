@@ -0,0 +1,133 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use lightyear::prelude::{server::*, *};
+
+use crate::{admin::AdminCommand, config::ServerConfig};
+
+const LOG_INTERVAL: Duration = Duration::from_secs(30);
+// The moving average covers about this much time
+const SMOOTHING_WINDOW: Duration = Duration::from_secs(1);
+
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BandwidthMetrics>()
+            .add_systems(
+                PostUpdate,
+                measure_bandwidth
+                    .after(TransportSet::Send)
+                    .before(LinkSet::Send),
+            )
+            .add_systems(
+                Update,
+                (
+                    log_bandwidth.run_if(on_timer(LOG_INTERVAL)),
+                    handle_admin_commands,
+                ),
+            )
+            .add_observer(remove_client);
+    }
+}
+
+/// Bytes per second sent to each client, replication and messages alike
+#[derive(Resource, Default, Debug)]
+pub struct BandwidthMetrics {
+    clients: HashMap<Entity, ClientBandwidth>,
+}
+
+#[derive(Default, Debug)]
+struct ClientBandwidth {
+    // Exponential moving average
+    bytes_per_second: f32,
+    total_bytes: u64,
+}
+
+impl BandwidthMetrics {
+    fn record(&mut self, client: Entity, bytes: usize, interval: Duration) {
+        let smoothing = (interval.as_secs_f32() / SMOOTHING_WINDOW.as_secs_f32()).min(1.0);
+
+        let client = self.clients.entry(client).or_default();
+        let rate = bytes as f32 / interval.as_secs_f32();
+        client.bytes_per_second += (rate - client.bytes_per_second) * smoothing;
+        client.total_bytes += bytes as u64;
+    }
+}
+
+// Sizes of the packets lightyear wrote to each client's link this frame, which
+// are what the bandwidth cap let through, read before the IO layer sends them off
+fn measure_bandwidth(
+    time: Res<Time>,
+    mut metrics: ResMut<BandwidthMetrics>,
+    clients: Query<(Entity, &Link), (With<ClientOf>, With<Connected>)>,
+) {
+    if time.delta().is_zero() {
+        return;
+    }
+
+    for (client, link) in &clients {
+        let bytes = link.send.iter().map(|packet| packet.len()).sum();
+        metrics.record(client, bytes, time.delta());
+    }
+}
+
+fn remove_client(trigger: Trigger<OnRemove, Connected>, mut metrics: ResMut<BandwidthMetrics>) {
+    metrics.clients.remove(&trigger.target());
+}
+
+fn describe(
+    metrics: &BandwidthMetrics,
+    config: &ServerConfig,
+    remote_ids: &Query<&RemoteId>,
+) -> Vec<String> {
+    let budget = config
+        .bandwidth_cap
+        .map_or("uncapped".to_string(), |bytes_per_second| {
+            format!("of {:.1} KB/s", bytes_per_second as f32 / 1000.0)
+        });
+
+    metrics
+        .clients
+        .iter()
+        .map(|(client, bandwidth)| {
+            let client_id = remote_ids
+                .get(*client)
+                .map(|remote_id| remote_id.0.to_bits());
+            format!(
+                "client {client_id:?}: {:.1} KB/s {budget}, {:.1} KB total",
+                bandwidth.bytes_per_second / 1000.0,
+                bandwidth.total_bytes as f32 / 1000.0
+            )
+        })
+        .collect()
+}
+
+fn log_bandwidth(
+    metrics: Res<BandwidthMetrics>,
+    config: Res<ServerConfig>,
+    remote_ids: Query<&RemoteId>,
+) {
+    for line in describe(&metrics, &config, &remote_ids) {
+        info!("Bandwidth {line}");
+    }
+}
+
+fn handle_admin_commands(
+    mut admin_commands: EventReader<AdminCommand>,
+    metrics: Res<BandwidthMetrics>,
+    config: Res<ServerConfig>,
+    remote_ids: Query<&RemoteId>,
+) {
+    for command in admin_commands.read() {
+        if *command == AdminCommand::Bandwidth {
+            let lines = describe(&metrics, &config, &remote_ids);
+            if lines.is_empty() {
+                info!("No clients connected");
+            }
+            for line in lines {
+                info!("{line}");
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use avian3d::prelude::*;
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::*;
+
+use crate::{SEND_INTERVAL, config::ServerConfig, instance::InInstance};
+
+// Lightyear adds these up every tick an entity's updates are held back by the
+// bandwidth cap, so the higher the base the sooner it gets its turn
+const OWN_CHARACTER_PRIORITY: f32 = 20.0;
+const CHARACTER_PRIORITY: f32 = 10.0;
+const NEAR_BLOCK_PRIORITY: f32 = 5.0;
+const FAR_BLOCK_PRIORITY: f32 = 1.0;
+const WORLD_ITEM_PRIORITY: f32 = 2.0;
+const FLOOR_PRIORITY: f32 = 0.1;
+
+pub struct PriorityPlugin;
+
+impl Plugin for PriorityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_priorities.run_if(on_timer(SEND_INTERVAL)))
+            .add_observer(set_character_priority)
+            .add_observer(set_block_priority)
+            .add_observer(set_world_item_priority)
+            .add_observer(set_floor_priority);
+    }
+}
+
+/// How an entity is ranked for replication, each client then gets its own
+/// priorities out of it
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub enum ReplicationPriority {
+    Character,
+    /// Ranked by how close it is to the client's character
+    Block,
+    Fixed(f32),
+}
+
+fn set_character_priority(
+    trigger: Trigger<OnAdd, character::CharacterMarker>,
+    mut commands: Commands,
+) {
+    commands
+        .entity(trigger.target())
+        .insert(ReplicationPriority::Character);
+}
+
+fn set_block_priority(trigger: Trigger<OnAdd, block::BlockMarker>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert(ReplicationPriority::Block);
+}
+
+fn set_world_item_priority(trigger: Trigger<OnAdd, world_item::WorldItem>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert(ReplicationPriority::Fixed(WORLD_ITEM_PRIORITY));
+}
+
+fn set_floor_priority(trigger: Trigger<OnAdd, floor::FloorMarker>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert(ReplicationPriority::Fixed(FLOOR_PRIORITY));
+}
+
+// Every entity is its own replication group, so the group id is the entity
+fn update_priorities(
+    config: Res<ServerConfig>,
+    mut clients: Query<(Entity, &mut ReplicationSender), (With<ClientOf>, With<Connected>)>,
+    characters: Query<
+        (Entity, &Position, &InInstance, &ControlledBy),
+        With<character::CharacterMarker>,
+    >,
+    replicated: Query<(
+        Entity,
+        &ReplicationPriority,
+        &Position,
+        Option<&InInstance>,
+        Option<&NetworkVisibility>,
+    )>,
+) {
+    let viewers: HashMap<Entity, (Entity, Vec3, Entity)> = characters
+        .iter()
+        .map(|(character, position, in_instance, controlled_by)| {
+            (controlled_by.owner, (character, position.0, in_instance.0))
+        })
+        .collect();
+
+    for (client, mut sender) in &mut clients {
+        let viewer = viewers.get(&client);
+
+        for (entity, kind, position, in_instance, visibility) in &replicated {
+            if visibility.is_some_and(|visibility| !visibility.is_visible(client)) {
+                continue;
+            }
+
+            let priority = match kind {
+                ReplicationPriority::Character
+                    if viewer.is_some_and(|(character, ..)| *character == entity) =>
+                {
+                    OWN_CHARACTER_PRIORITY
+                }
+                ReplicationPriority::Character => CHARACTER_PRIORITY,
+                // Blocks close to the client's character are the ones it bumps into and watches
+                ReplicationPriority::Block => {
+                    let closeness = viewer
+                        .filter(|(_, _, viewer_instance)| {
+                            in_instance.is_some_and(|in_instance| in_instance.0 == *viewer_instance)
+                        })
+                        .map(|(_, viewer, _)| {
+                            1.0 - (viewer.distance(position.0) / config.interest_radius)
+                                .clamp(0.0, 1.0)
+                        })
+                        .unwrap_or_default();
+                    FAR_BLOCK_PRIORITY + (NEAR_BLOCK_PRIORITY - FAR_BLOCK_PRIORITY) * closeness
+                }
+                ReplicationPriority::Fixed(priority) => *priority,
+            };
+
+            sender.update_base_priority(ReplicationGroupId(entity.to_bits()), priority);
+        }
+    }
+}
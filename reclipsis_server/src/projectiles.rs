@@ -0,0 +1,236 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::{
+    character::CharacterMarker,
+    item::{ItemKind, ItemRegistry},
+    projectile::Projectile,
+};
+use reclipsis_common::{
+    item_use::{ItemUsed, UseAction},
+    projectile::{ProjectileHit, detect_projectile_hits, prespawn_projectile},
+};
+
+use crate::{
+    health::{Damage, DamageSource},
+    instance::InInstance,
+    interest::InterestManaged,
+};
+
+pub struct ProjectilesPlugin;
+
+impl Plugin for ProjectilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                spawn_projectiles
+                    .after(crate::handle_character_actions)
+                    .run_if(resource_exists::<ItemRegistry>),
+                handle_projectile_hits
+                    .after(detect_projectile_hits)
+                    .run_if(resource_exists::<ItemRegistry>),
+                expire_projectiles,
+            )
+                .before(crate::health::apply_damage),
+        );
+    }
+}
+
+/// Character that shot a projectile, it can't hit itself
+#[derive(Component, Clone, Copy, Debug)]
+struct Shooter(Entity);
+
+#[derive(Component, Debug)]
+struct Lifetime(Timer);
+
+// The shooter's client spawns the same projectile on the same tick
+fn spawn_projectiles(
+    mut commands: Commands,
+    registry: Res<ItemRegistry>,
+    mut item_used: EventReader<ItemUsed>,
+    characters: Query<
+        (
+            &Position,
+            &Rotation,
+            &InInstance,
+            &CollisionLayers,
+            &ControlledBy,
+        ),
+        With<CharacterMarker>,
+    >,
+    clients: Query<&RemoteId, With<ClientOf>>,
+) {
+    for used in item_used.read() {
+        if used.action != UseAction::Primary {
+            continue;
+        }
+        let Some(item) = registry.get(used.item) else {
+            continue;
+        };
+        let ItemKind::Ranged { lifetime_secs, .. } = item.kind else {
+            continue;
+        };
+        let Ok((position, rotation, in_instance, collision_layers, controlled_by)) =
+            characters.get(used.character)
+        else {
+            continue;
+        };
+        let Ok(remote_id) = clients.get(controlled_by.owner) else {
+            continue;
+        };
+        let Some(projectile) =
+            prespawn_projectile(item, position.0, rotation.0, *collision_layers, remote_id.0)
+        else {
+            continue;
+        };
+
+        commands.spawn((
+            projectile,
+            Replicate::to_clients(NetworkTarget::All),
+            PredictionTarget::to_clients(NetworkTarget::All),
+            InterestManaged,
+            *in_instance,
+            Shooter(used.character),
+            Lifetime(Timer::from_seconds(lifetime_secs, TimerMode::Once)),
+        ));
+    }
+}
+
+fn handle_projectile_hits(
+    mut commands: Commands,
+    registry: Res<ItemRegistry>,
+    mut hits: EventReader<ProjectileHit>,
+    projectiles: Query<(&Projectile, &Shooter)>,
+    characters: Query<(), With<CharacterMarker>>,
+    mut damage: EventWriter<Damage>,
+) {
+    for hit in hits.read() {
+        let Ok((projectile, shooter)) = projectiles.get(hit.projectile) else {
+            continue;
+        };
+        // Projectiles start outside the shooter, but it can run into them
+        if hit.target == shooter.0 {
+            continue;
+        }
+
+        if characters.contains(hit.target) {
+            if let Some(ItemKind::Ranged { damage: amount, .. }) =
+                registry.get(projectile.item).map(|item| &item.kind)
+            {
+                damage.write(Damage {
+                    target: hit.target,
+                    amount: *amount,
+                    source: DamageSource::Item {
+                        attacker: shooter.0,
+                        item: projectile.item,
+                    },
+                });
+            }
+        }
+
+        // A projectile can hit several things or expire on the same tick
+        commands.entity(hit.projectile).try_despawn();
+    }
+}
+
+fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectiles: Query<(Entity, &mut Lifetime)>,
+) {
+    for (entity, mut lifetime) in &mut projectiles {
+        if lifetime.0.tick(time.delta()).finished() {
+            commands.entity(entity).try_despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use reclipsis_assets::{inventory::ItemId, item::Item};
+
+    use super::*;
+
+    const SLINGSHOT: ItemId = ItemId(2);
+
+    fn slingshot() -> Item {
+        Item {
+            id: SLINGSHOT,
+            name: "Slingshot".to_string(),
+            description: String::new(),
+            icon: String::new(),
+            max_stack: 1,
+            kind: ItemKind::Ranged {
+                damage: 15.0,
+                projectile_speed: 30.0,
+                gravity_scale: 0.5,
+                lifetime_secs: 3.0,
+                cooldown_secs: 0.8,
+            },
+        }
+    }
+
+    // The client's `spawn_predicted_projectiles` test expects the same values for
+    // this shot
+    #[test]
+    fn shots_spawn_the_projectile_the_client_predicts() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<ItemUsed>()
+            .insert_resource(ItemRegistry::new([slingshot()]).unwrap())
+            .add_systems(Update, spawn_projectiles);
+
+        let peer = PeerId::Netcode(7);
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        // Facing -X
+        let rotation = Quat::from_rotation_y(FRAC_PI_2);
+        let layers = CollisionLayers::new(LayerMask(1 << 2), LayerMask(1 << 2));
+
+        let client = app.world_mut().spawn((ClientOf, RemoteId(peer))).id();
+        let instance = app.world_mut().spawn_empty().id();
+        let character = app
+            .world_mut()
+            .spawn((
+                CharacterMarker,
+                Position(position),
+                Rotation(rotation),
+                InInstance(instance),
+                layers,
+                ControlledBy {
+                    owner: client,
+                    lifetime: Default::default(),
+                },
+            ))
+            .id();
+        app.world_mut().send_event(ItemUsed {
+            character,
+            item: SLINGSHOT,
+            slot: 0,
+            action: UseAction::Primary,
+        });
+        app.update();
+
+        let mut projectiles = app
+            .world_mut()
+            .query_filtered::<Entity, (With<Projectile>, With<Shooter>)>();
+        let projectile = projectiles.single(app.world()).unwrap();
+
+        // Just outside the capsule, at 30 m/s aimed 0.1 upwards
+        let world = app.world();
+        assert_eq!(world.get::<Shooter>(projectile).unwrap().0, character);
+        let launched_at = world.get::<Position>(projectile).unwrap().0;
+        assert!(launched_at.abs_diff_eq(Vec3::new(0.3, 2.0, 3.0), 1e-5));
+        let velocity = world.get::<LinearVelocity>(projectile).unwrap().0;
+        let expected = Vec3::new(-1.0, 0.1, 0.0) * 30.0 / 1.01f32.sqrt();
+        assert!(velocity.abs_diff_eq(expected, 1e-4));
+        assert_eq!(world.get::<CollisionLayers>(projectile), Some(&layers));
+
+        // Lightyear computes the hash from the spawn tick and this salt after the spawn
+        let prespawned = world.get::<PreSpawned>(projectile).unwrap();
+        assert_eq!(prespawned.user_salt, Some(peer.to_bits()));
+        assert_eq!(prespawned.hash, None);
+    }
+}
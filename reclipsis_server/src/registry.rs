@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::{block::BlockRegistry, definitions::Registry, item::ItemRegistry};
+use reclipsis_common::protocol::*;
+
+pub struct RegistryPlugin;
+
+impl Plugin for RegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(send_registry_checksum);
+    }
+}
+
+// The server only starts once the registries are loaded, see `start_server`
+fn send_registry_checksum(
+    trigger: Trigger<OnAdd, Connected>,
+    items: Res<ItemRegistry>,
+    blocks: Res<BlockRegistry>,
+    mut senders: Query<&mut MessageSender<RegistryChecksum>, With<ClientOf>>,
+) {
+    let Ok(mut sender) = senders.get_mut(trigger.target()) else {
+        return;
+    };
+
+    sender.send::<ReliableChannel>(RegistryChecksum {
+        items: items.checksum(),
+        blocks: blocks.checksum(),
+    });
+}
@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use avian3d::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use rand::Rng;
+use reclipsis_assets::{
+    character::CharacterMarker,
+    health::{Dead, Health},
+    spawn::{KILL_PLANE_HEIGHT, SpawnPoint},
+};
+
+use crate::{
+    anticheat::InputValidation,
+    config::ServerConfig,
+    health::{Damage, DamageSource},
+    instance::InInstance,
+};
+
+// Characters closer than this to a spawn point count towards its crowd
+const CROWD_RADIUS: f32 = 5.0;
+
+const FALLBACK_SPAWN: Vec3 = Vec3::new(0.0, 5.0, 0.0);
+
+pub struct RespawnPlugin;
+
+impl Plugin for RespawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RoundRobinIndex>().add_systems(
+            FixedUpdate,
+            (kill_fallen_characters, respawn_dead_characters)
+                .before(crate::handle_character_actions),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpawnPolicy {
+    Random,
+    #[default]
+    RoundRobin,
+    LeastCrowded,
+}
+
+impl FromStr for SpawnPolicy {
+    type Err = ();
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "random" => Ok(Self::Random),
+            "round-robin" => Ok(Self::RoundRobin),
+            "least-crowded" => Ok(Self::LeastCrowded),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+struct RoundRobinIndex(usize);
+
+/// Counts down until a dead character respawns
+#[derive(Component, Debug)]
+pub struct Respawning(Timer);
+
+impl Respawning {
+    pub fn new(delay_secs: f32) -> Self {
+        Self(Timer::from_seconds(delay_secs, TimerMode::Once))
+    }
+}
+
+#[derive(SystemParam)]
+pub struct SpawnSelector<'w, 's> {
+    config: Res<'w, ServerConfig>,
+    round_robin: ResMut<'w, RoundRobinIndex>,
+    spawn_points: Query<'w, 's, (&'static Transform, &'static InInstance), With<SpawnPoint>>,
+    characters: Query<'w, 's, (&'static Position, &'static InInstance), With<CharacterMarker>>,
+}
+
+impl SpawnSelector<'_, '_> {
+    /// Picks a spawn point in `instance` according to the configured `SpawnPolicy`
+    pub fn select(&mut self, instance: Entity) -> Transform {
+        let mut spawn_points: Vec<&Transform> = self
+            .spawn_points
+            .iter()
+            .filter(|(_, in_instance)| in_instance.0 == instance)
+            .map(|(transform, _)| transform)
+            .collect();
+        if spawn_points.is_empty() {
+            warn!("Instance has no spawn points, spawning at {FALLBACK_SPAWN}");
+            return Transform::from_translation(FALLBACK_SPAWN);
+        }
+
+        // Query order isn't meaningful, keep round-robin stable between calls
+        spawn_points.sort_by(|a, b| {
+            a.translation
+                .to_array()
+                .partial_cmp(&b.translation.to_array())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let index = match self.config.spawn_policy {
+            SpawnPolicy::Random => rand::rng().random_range(0..spawn_points.len()),
+            SpawnPolicy::RoundRobin => {
+                let index = self.round_robin.0 % spawn_points.len();
+                self.round_robin.0 = index + 1;
+                index
+            }
+            SpawnPolicy::LeastCrowded => {
+                let crowd = |spawn_point: &Transform| {
+                    self.characters
+                        .iter()
+                        .filter(|(position, in_instance)| {
+                            in_instance.0 == instance
+                                && position.distance(spawn_point.translation) < CROWD_RADIUS
+                        })
+                        .count()
+                };
+
+                (0..spawn_points.len())
+                    .min_by_key(|index| crowd(spawn_points[*index]))
+                    .unwrap_or_default()
+            }
+        };
+
+        *spawn_points[index]
+    }
+}
+
+fn kill_fallen_characters(
+    characters: Query<(Entity, &Position, &Health), (With<CharacterMarker>, Without<Dead>)>,
+    mut damage: EventWriter<Damage>,
+) {
+    for (entity, position, health) in &characters {
+        if position.y < KILL_PLANE_HEIGHT {
+            damage.write(Damage {
+                target: entity,
+                amount: health.current,
+                source: DamageSource::Fall,
+            });
+        }
+    }
+}
+
+// The selector reads character positions, so it can't be used while they are borrowed
+fn respawn_dead_characters(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut params: ParamSet<(
+        SpawnSelector,
+        Query<
+            (
+                &mut Position,
+                &mut Rotation,
+                &mut LinearVelocity,
+                &mut AngularVelocity,
+                &mut Transform,
+                &mut Health,
+                Option<&mut InputValidation>,
+            ),
+            With<CharacterMarker>,
+        >,
+        Query<(Entity, &InInstance, &mut Respawning)>,
+    )>,
+) {
+    let ready: Vec<(Entity, Entity)> = params
+        .p2()
+        .iter_mut()
+        .filter_map(|(entity, in_instance, mut respawning)| {
+            respawning
+                .0
+                .tick(time.delta())
+                .finished()
+                .then_some((entity, in_instance.0))
+        })
+        .collect();
+
+    for (entity, instance) in ready {
+        let spawn = params.p0().select(instance);
+
+        let mut query = params.p1();
+        let Ok((
+            mut position,
+            mut rotation,
+            mut linear_velocity,
+            mut angular_velocity,
+            mut transform,
+            mut health,
+            validation,
+        )) = query.get_mut(entity)
+        else {
+            continue;
+        };
+
+        info!("Respawning character {entity:?} at {}", spawn.translation);
+
+        position.0 = spawn.translation;
+        rotation.0 = spawn.rotation;
+        linear_velocity.0 = Vec3::ZERO;
+        angular_velocity.0 = Vec3::ZERO;
+        // Keep the transform in sync, or physics would copy the old one back
+        *transform = spawn;
+        health.current = health.max;
+
+        if let Some(mut validation) = validation {
+            validation.reset_position();
+        }
+
+        commands.entity(entity).remove::<(Dead, Respawning)>();
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use lightyear::prelude::{server::*, *};
+use reclipsis_assets::{
+    character::CharacterMarker,
+    voxel::{self, BlockId, VoxelChunk, VoxelWorld},
+};
+use reclipsis_common::protocol::{ReliableChannel, WorldUpdate};
+
+use crate::instance::{GameInstance, InInstance};
+
+pub struct VoxelPlugin;
+
+impl Plugin for VoxelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CellUpdated>()
+            .add_systems(Update, (send_worlds, send_cell_updates).chain())
+            .add_observer(add_sent_world)
+            .add_observer(add_chunk_instance);
+    }
+}
+
+/// Sends the current contents of `cell` to the clients in `world`, after it is
+/// edited or to undo a rejected prediction
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CellUpdated {
+    pub world: Entity,
+    pub cell: IVec3,
+}
+
+/// Instance whose world the client was sent, it gets the edits made to it
+#[derive(Component, Default, Debug)]
+pub struct SentWorld(Option<Entity>);
+
+fn add_sent_world(trigger: Trigger<OnAdd, ClientOf>, mut commands: Commands) {
+    commands
+        .entity(trigger.target())
+        .insert(SentWorld::default());
+}
+
+// Worlds are the instance entities, chunks collide with what's in the instance
+fn add_chunk_instance(
+    trigger: Trigger<OnAdd, VoxelChunk>,
+    mut commands: Commands,
+    chunks: Query<&VoxelChunk>,
+    instances: Query<&GameInstance>,
+) {
+    let Ok(chunk) = chunks.get(trigger.target()) else {
+        return;
+    };
+    let Ok(instance) = instances.get(chunk.world) else {
+        return;
+    };
+
+    commands
+        .entity(trigger.target())
+        .insert((InInstance(chunk.world), instance.collision_layers()));
+}
+
+// Clients get the whole world when their character enters an instance, and only
+// the edits afterwards
+pub fn send_worlds(
+    characters: Query<(&ControlledBy, &InInstance), With<CharacterMarker>>,
+    mut clients: Query<(&mut MessageSender<WorldUpdate>, &mut SentWorld), With<ClientOf>>,
+    worlds: Query<(&GameInstance, &VoxelWorld)>,
+) {
+    for (controlled_by, in_instance) in &characters {
+        let Ok((mut sender, mut sent_world)) = clients.get_mut(controlled_by.owner) else {
+            continue;
+        };
+        if sent_world.0 == Some(in_instance.0) {
+            continue;
+        }
+        let Ok((instance, world)) = worlds.get(in_instance.0) else {
+            continue;
+        };
+
+        sender.send::<ReliableChannel>(WorldUpdate::Reset {
+            allows_building: instance.allows_building(),
+        });
+        for (pos, chunk) in world.chunks() {
+            sender.send::<ReliableChannel>(WorldUpdate::Chunk {
+                pos,
+                chunk: chunk.compress(),
+            });
+        }
+        sent_world.0 = Some(in_instance.0);
+    }
+}
+
+fn send_cell_updates(
+    mut updates: EventReader<CellUpdated>,
+    worlds: Query<&VoxelWorld>,
+    mut clients: Query<(&mut MessageSender<WorldUpdate>, &SentWorld), With<ClientOf>>,
+) {
+    // Edits are batched per chunk, a cell updated twice is sent twice with the
+    // same contents
+    let mut edits: HashMap<(Entity, IVec3), Vec<(u16, BlockId)>> = HashMap::new();
+    for update in updates.read() {
+        let Ok(world) = worlds.get(update.world) else {
+            continue;
+        };
+        let (chunk, index) = voxel::split_cell(update.cell);
+        edits
+            .entry((update.world, chunk))
+            .or_default()
+            .push((index, world.get(update.cell)));
+    }
+
+    for ((world, chunk), edits) in edits {
+        for (mut sender, sent_world) in &mut clients {
+            if sent_world.0 == Some(world) {
+                sender.send::<ReliableChannel>(WorldUpdate::Edits {
+                    chunk,
+                    edits: edits.clone(),
+                });
+            }
+        }
+    }
+}